@@ -0,0 +1,514 @@
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Settings kept between sessions.
+// Missing fields fall back to the defaults, so older config files still load.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub output_dir: PathBuf,
+    pub test_yield: f32, // 0-100%, fractions included
+    pub high_fail_ratio: u8,
+    pub marginal_rate: u8,
+    pub cluster_rate: u8,
+    pub corrupt_rate: u8, // 0-100%, of the ICT logs written truncated; keep it 0 for valid logs
+    pub fail_margin: f32, // 0-100%, of the limit
+    pub temperature_cycle: Option<TemperatureCycle>,
+    pub resolution: Option<Resolution>,
+    pub testing_time: f64,
+    pub testing_jitter: f64,
+    pub burst: u32,          // multiboards written at once, every testing time
+    pub board_time_min: f64, // in seconds, test time of a board written into its log
+    pub board_time_max: f64,
+    pub board_delay: f64, // in seconds, between the boards of a multiboard
+    pub seed: Option<u64>,
+    pub last_id: u32,
+    pub last_id_date: Option<String>, // YYYY-MM-DD, the serials restart from 1 on a new day
+    pub line_id: String,
+    pub station_id: String,
+    pub btest: BTestFields,
+    pub operator: String,
+    pub fixture_id: String,
+    pub sw_rev: String,
+    pub filename_template: String,
+    pub extension: String,
+    pub daily_file: bool,
+    pub precision: Option<u8>,
+    pub summary: bool,
+    pub csv_export: bool,
+    pub iso_timestamps: bool,
+    pub dry_run: bool, // only print the paths of the files, without writing them
+    pub write_retries: u32, // a failed write is tried this many more times before it's an error
+    pub output_format: OutputFormat,
+    pub line_ending: LineEnding,
+    pub trailing_newline: bool,
+    pub index_scheme: IndexScheme,
+    pub subfolders: Subfolders,
+    pub sim_start: Option<String>, // YYYY-MM-DD HH:MM:SS, local time; None uses the real time
+    pub schedule: Option<Schedule>, // working hours of the virtual clock
+    pub board_types: Vec<BoardTypeConfig>,
+    pub type_order: TypeOrder,
+    pub test_order: TestOrder,
+    pub retest_rate: u8,
+    pub retest_delay: f64,
+    pub forced_failures: Vec<ForcedFailure>,
+    pub single_defect: bool,
+    pub pin_detail: bool,
+    pub full_scale: Vec<FullScale>, // none by default, nothing is railed
+    pub railed_value: RailedValue,
+    pub remote_port: Option<u16>, // HTTP remote control of the station in the GUI, off if None
+    pub dark_mode: bool,          // GUI theme
+    pub stations: Vec<Config>,    // more stations in the GUI, each with its own settings
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoardTypeConfig {
+    pub name: String,
+    pub panels: u8,
+    pub dmc_template: String,
+    pub shared_dmc: bool,
+    pub check_digit: CheckDigit, // appended to the DMCs
+    pub tests_file: Option<PathBuf>,
+    pub random_tests: TestCounts,      // used without a tests_file
+    pub nominal_ranges: NominalRanges, // of the random tests
+}
+
+// Check character at the end of the DMCs, validated by the scanners
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum CheckDigit {
+    #[default]
+    None,
+    Mod43, // Code 39 style, over the whole DMC
+    Mod10, // GS1 style, over its digits
+}
+
+// A test of the JSON test definition files, the same fields as the CSV ones (see load_tests).
+// The limits are null for pass/fail only tests.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TestDefinition {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ttype: String, // TType name
+    pub min: Option<f32>,
+    pub nom: Option<f32>,
+    pub max: Option<f32>,
+    pub fail_rate: Option<u8>,
+    pub drift: Option<f32>,
+    pub tempco: Option<f32>,
+    pub limits: String, // LimitFormat name, empty for lim3
+    pub extra: Vec<ExtraDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ExtraDefinition {
+    pub unit: String,
+    pub min: f32,
+    pub nom: f32,
+    pub max: f32,
+}
+
+// Number of the generated tests of each type, besides the pins test
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TestCounts {
+    pub jumpers: u32,
+    pub capacitors: u32,
+    pub resistors: u32,
+    pub inductors: u32,
+    pub diodes: u32,
+    pub zeners: u32,
+    pub leds: u32,
+    pub transistors: u32,
+}
+
+impl Default for TestCounts {
+    fn default() -> Self {
+        Self {
+            jumpers: 2,
+            capacitors: 10,
+            resistors: 10,
+            inductors: 5,
+            diodes: 5,
+            zeners: 1,
+            leds: 1,
+            transistors: 2,
+        }
+    }
+}
+
+// Ranges of the nominal values of the random capacitors (F) and resistors (Ω), (from, to).
+// The values are spread evenly in them.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct NominalRanges {
+    pub capacitors: (f32, f32),
+    pub resistors: (f32, f32),
+}
+
+impl Default for NominalRanges {
+    fn default() -> Self {
+        Self {
+            capacitors: (1E-12, 1E-6),
+            resistors: (1E0, 1E6),
+        }
+    }
+}
+
+// How the board type of the next multiboard is choosen
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum TypeOrder {
+    #[default]
+    Cycle,
+    Random,
+}
+
+// Ambient temperature of the simulated tester, a sine around 25°C.
+// The measurements shift with it by the temperature coefficients of the tests.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TemperatureCycle {
+    pub amplitude: f32, // °C
+    pub period: f32,    // hours
+}
+
+impl Default for TemperatureCycle {
+    fn default() -> Self {
+        Self {
+            amplitude: 10.0,
+            period: 24.0,
+        }
+    }
+}
+
+impl TemperatureCycle {
+    // °C from 25°C, the given hours after the start
+    pub fn get_offset(&self, hours: f32) -> f32 {
+        if self.period <= 0.0 {
+            return 0.0;
+        }
+        self.amplitude * (hours / self.period * std::f32::consts::TAU).sin()
+    }
+}
+
+// The fixed fields of @BTEST, named as in the 3070 log format:
+// {@BTEST|board id|status|start|duration|multiple test|log level|log set|learning|known good|
+// end|status qualifier|board number|parent panel id
+// The others come from the board, the duration too. "multiple test" is set on the retests,
+// and "known good" on the golden boards too.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct BTestFields {
+    pub log_level: String,        // "all"
+    pub log_set: String,          // empty
+    pub learning: bool,           // n
+    pub known_good: bool,         // n
+    pub status_qualifier: String, // empty
+}
+
+impl Default for BTestFields {
+    fn default() -> Self {
+        Self {
+            log_level: "all".to_string(),
+            log_set: String::new(),
+            learning: false,
+            known_good: false,
+            status_qualifier: String::new(),
+        }
+    }
+}
+
+// Shifts of the virtual clock: it skips from the end of one to the start of the next,
+// so the boards are tested only in the working hours of the active days.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Schedule {
+    pub start_hour: u32,     // 0-23
+    pub end_hour: u32,       // 1-24, after start_hour
+    pub weekdays: [bool; 7], // active days, from Monday
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            start_hour: 6,
+            end_hour: 22,
+            weekdays: [true, true, true, true, true, false, false],
+        }
+    }
+}
+
+impl Schedule {
+    // The time itself if it's in a shift, or else the start of the next one.
+    // An invalid schedule (no active day, or no hours) doesn't skip anything.
+    pub fn next_active(&self, time: NaiveDateTime) -> NaiveDateTime {
+        let (start, end) = (self.start_hour, self.end_hour.min(24));
+        if start >= end || !self.weekdays.contains(&true) {
+            return time;
+        }
+
+        let mut date = time.date();
+        // at most a week ahead, e.g. from Friday evening to Monday morning
+        for _ in 0..8 {
+            if self.weekdays[date.weekday().num_days_from_monday() as usize] {
+                let shift_start = date.and_hms_opt(start, 0, 0).unwrap_or(time);
+                if date != time.date() || time.hour() < start {
+                    return shift_start;
+                }
+                if time.hour() < end {
+                    return time;
+                }
+            }
+            date = match date.succ_opt() {
+                Some(next) => next,
+                None => return time,
+            };
+        }
+
+        time
+    }
+}
+
+// Order of the tests in the ICT logfiles. The other outputs keep the order of the tests list.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub enum TestOrder {
+    #[default]
+    Defined, // as in the tests list
+    Shuffled,            // randomly, for every board
+    Custom(Vec<String>), // the named tests first in this order, then the rest as defined
+}
+
+// Logfile format(s) written for each board. JSON files get a .json extension.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Ict,
+    Json,
+    Both,
+}
+
+// Line endings of the ICT logs. The default is the one of the platform.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+impl Default for LineEnding {
+    fn default() -> Self {
+        if cfg!(windows) {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        }
+    }
+}
+
+// Numbering of the board positions in @PF, @BLOCK and the filenames.
+// @BTEST, the JSON and the summary.csv keep the tester's own, 1-based, numbers.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum IndexScheme {
+    #[default]
+    OneBased,
+    ZeroBased,
+    Letters, // A-Z, then AA, AB...
+}
+
+impl IndexScheme {
+    // index is 1-based, like Board::index
+    pub fn format(&self, index: u8) -> String {
+        match self {
+            IndexScheme::OneBased => index.to_string(),
+            IndexScheme::ZeroBased => index.saturating_sub(1).to_string(),
+            IndexScheme::Letters => {
+                let mut ret = String::new();
+                let mut n = index.max(1) as u32;
+                while n > 0 {
+                    n -= 1;
+                    ret.insert(0, (b'A' + (n % 26) as u8) as char);
+                    n /= 26;
+                }
+                ret
+            }
+        }
+    }
+}
+
+// Subfolder of output_dir for the files of each multiboard
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum Subfolders {
+    #[default]
+    None,
+    Dmc,  // named after the multiboard's DMC
+    Time, // named after the end of its test, %y%m%d%H%M%S
+}
+
+// Step of the analog measurements, like the ADC of a real tester
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    Relative(f32), // % of the nominal value of the test
+    Absolute(f32), // in the unit of the test
+}
+
+impl Resolution {
+    pub fn get_step(&self, nom: f32) -> f32 {
+        match *self {
+            Resolution::Relative(percent) => (nom * percent / 100.0).abs(),
+            Resolution::Absolute(step) => step,
+        }
+    }
+}
+
+// Range of the tester for a unit, the measurements beyond it are railed:
+// clamped to +-value, and logged as set by RailedValue
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct FullScale {
+    pub unit: String,
+    pub value: f32,
+}
+
+// How a railed measurement is logged
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum RailedValue {
+    #[default]
+    Ovld, // "OVLD" instead of the value
+    FullScale, // the full scale value, with the sign of the measurement
+}
+
+// A test which always fails on the given board(s), for reproducing a specific log
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ForcedFailure {
+    pub test: String,
+    pub board: String, // DMC or panel index of the board, empty for every board
+}
+
+impl ForcedFailure {
+    pub fn matches(&self, DMC: &str, index: u8) -> bool {
+        let board = self.board.trim();
+        board.is_empty() || board == DMC || board.parse() == Ok(index)
+    }
+}
+
+impl Default for BoardTypeConfig {
+    fn default() -> Self {
+        Self {
+            name: "Board".to_string(),
+            panels: 20,
+            dmc_template: "L{line}{year}{doy}{serial}TB0001010111".to_string(),
+            shared_dmc: false,
+            check_digit: CheckDigit::None,
+            tests_file: None,
+            random_tests: TestCounts::default(),
+            nominal_ranges: NominalRanges::default(),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output_dir: Self::default_output_dir(),
+            test_yield: 99.0,
+            high_fail_ratio: 50,
+            marginal_rate: 0,
+            cluster_rate: 0,
+            corrupt_rate: 0,
+            fail_margin: 0.0,
+            temperature_cycle: None,
+            resolution: None,
+            testing_time: 30.0,
+            testing_jitter: 0.0,
+            burst: 1,
+            board_time_min: 8.0,
+            board_time_max: 15.0,
+            board_delay: 0.0,
+            seed: None,
+            last_id: 1,
+            last_id_date: None,
+            line_id: "01".to_string(),
+            station_id: "0101".to_string(),
+            btest: BTestFields::default(),
+            operator: String::new(),
+            fixture_id: String::new(),
+            sw_rev: String::new(),
+            filename_template: "{index}-{time}-{counter}I3070CE{station}BZ01".to_string(),
+            extension: String::new(),
+            daily_file: false,
+            precision: None,
+            summary: true,
+            csv_export: false,
+            iso_timestamps: false,
+            dry_run: false,
+            write_retries: crate::writer::DEFAULT_RETRIES,
+            output_format: OutputFormat::Ict,
+            line_ending: LineEnding::default(),
+            trailing_newline: false,
+            index_scheme: IndexScheme::OneBased,
+            subfolders: Subfolders::None,
+            sim_start: None,
+            schedule: None,
+            board_types: vec![BoardTypeConfig::default()],
+            type_order: TypeOrder::Cycle,
+            test_order: TestOrder::Defined,
+            retest_rate: 0,
+            retest_delay: 60.0,
+            forced_failures: Vec::new(),
+            single_defect: false,
+            pin_detail: false,
+            full_scale: Vec::new(),
+            railed_value: RailedValue::Ovld,
+            remote_port: None,
+            dark_mode: true,
+            stations: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    // The config is saved next to the executable
+    fn path() -> io::Result<PathBuf> {
+        let exe = std::env::current_exe()?;
+        Ok(exe.with_file_name("ICT_log_maker.json"))
+    }
+
+    // Works on every platform. It is created on startup, if it's missing.
+    pub fn default_output_dir() -> PathBuf {
+        std::env::temp_dir().join("ICT_logs")
+    }
+
+    // Returns the default config if there is no saved one, or it can't be read
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| Self::load_from(&path))
+            .unwrap_or_default()
+    }
+
+    pub fn load_from(path: &Path) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        self.save_to(&Self::path()?)
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, text)
+    }
+}
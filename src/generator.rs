@@ -0,0 +1,2870 @@
+use rand::prelude::*;
+use rand_distr::Normal;
+use std::cell::Cell;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use chrono::{prelude::*, Duration};
+
+use crate::config::{
+    BTestFields, BoardTypeConfig, CheckDigit, Config, ExtraDefinition, ForcedFailure, FullScale,
+    IndexScheme, LineEnding, NominalRanges, OutputFormat, RailedValue, Resolution, Schedule,
+    Subfolders, TemperatureCycle, TestCounts, TestDefinition, TestOrder, TypeOrder,
+};
+use crate::json::{JsonBoard, JsonLimits, JsonTest};
+use crate::writer::Writer;
+
+// Test type + limits (min, nom, max)
+#[derive(Clone)]
+pub enum TType {
+    Pin,
+    Jumper,
+    Capacitor(f32, f32, f32),
+    Resistor(f32, f32, f32),
+    Diode(f32, f32, f32),
+    Zener(f32, f32, f32),
+    Led(f32, f32, f32),
+    Inductor(f32, f32, f32),
+    Transistor(f32, f32, f32), // limits are shared by the BE and BC junctions
+}
+
+impl TType {
+    pub const NAMES: [&'static str; 9] = [
+        "pin",
+        "jumper",
+        "capacitor",
+        "resistor",
+        "diode",
+        "zener",
+        "led",
+        "inductor",
+        "transistor",
+    ];
+
+    // Type names used in the test definition files
+    pub fn from_name(name: &str, min: f32, nom: f32, max: f32) -> Option<TType> {
+        match name.to_lowercase().as_str() {
+            "pin" => Some(TType::Pin),
+            "jumper" => Some(TType::Jumper),
+            "capacitor" => Some(TType::Capacitor(min, nom, max)),
+            "resistor" => Some(TType::Resistor(min, nom, max)),
+            "diode" => Some(TType::Diode(min, nom, max)),
+            "zener" => Some(TType::Zener(min, nom, max)),
+            "led" => Some(TType::Led(min, nom, max)),
+            "inductor" => Some(TType::Inductor(min, nom, max)),
+            "transistor" => Some(TType::Transistor(min, nom, max)),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            TType::Pin => "pin",
+            TType::Jumper => "jumper",
+            TType::Capacitor(..) => "capacitor",
+            TType::Resistor(..) => "resistor",
+            TType::Diode(..) => "diode",
+            TType::Zener(..) => "zener",
+            TType::Led(..) => "led",
+            TType::Inductor(..) => "inductor",
+            TType::Transistor(..) => "transistor",
+        }
+    }
+
+    // (min, nom, max), None for pass/fail only tests
+    pub fn get_limits(&self) -> Option<(f32, f32, f32)> {
+        match *self {
+            TType::Pin | TType::Jumper => None,
+            TType::Capacitor(min, nom, max)
+            | TType::Resistor(min, nom, max)
+            | TType::Diode(min, nom, max)
+            | TType::Zener(min, nom, max)
+            | TType::Led(min, nom, max)
+            | TType::Inductor(min, nom, max)
+            | TType::Transistor(min, nom, max) => Some((min, nom, max)),
+        }
+    }
+
+    pub fn get_limits_mut(&mut self) -> Option<(&mut f32, &mut f32, &mut f32)> {
+        match self {
+            TType::Pin | TType::Jumper => None,
+            TType::Capacitor(min, nom, max)
+            | TType::Resistor(min, nom, max)
+            | TType::Diode(min, nom, max)
+            | TType::Zener(min, nom, max)
+            | TType::Led(min, nom, max)
+            | TType::Inductor(min, nom, max)
+            | TType::Transistor(min, nom, max) => Some((min, nom, max)),
+        }
+    }
+
+    // Unit of the measurement, None for pass/fail only tests
+    pub fn get_unit(&self) -> Option<&str> {
+        match self {
+            TType::Pin | TType::Jumper => None,
+            TType::Capacitor(..) => Some("F"),
+            TType::Resistor(..) => Some("Ω"),
+            TType::Inductor(..) => Some("H"),
+            TType::Diode(..) | TType::Zener(..) | TType::Led(..) | TType::Transistor(..) => {
+                Some("V")
+            }
+        }
+    }
+
+    // Temperature coefficient in ppm/°C, for the types which have a notable one
+    pub fn default_tempco(&self) -> Option<f32> {
+        match self {
+            TType::Resistor(..) => Some(100.0),   // thick film
+            TType::Capacitor(..) => Some(-750.0), // class 1 dielectric, like N750
+            _ => None,
+        }
+    }
+
+    // Tag used for the analog measurement line in the logfile
+    pub fn get_tag(&self) -> &str {
+        match self {
+            TType::Pin => "PF",
+            TType::Jumper => "A-JUM",
+            TType::Capacitor(..) => "A-CAP",
+            TType::Resistor(..) => "A-RES",
+            TType::Diode(..) => "A-DIO",
+            TType::Zener(..) => "A-ZEN",
+            TType::Led(..) => "A-LED",
+            TType::Inductor(..) => "A-IND",
+            TType::Transistor(..) => "A-DIO",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Test {
+    pub name: String,
+    pub ttype: TType,
+    pub fail_rate: Option<u8>, // 0-100%, overrides the global yield if set
+    pub drift: Option<f32>,    // units per hour, added to the measurements
+    pub tempco: Option<f32>,   // ppm/°C, overrides TType::default_tempco
+    pub extra: Vec<Measurement>, // more lines in the block of an analog test, after its own
+    pub limit_format: LimitFormat,
+}
+
+// Limit block of the measurement lines of an analog test
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum LimitFormat {
+    #[default]
+    Lim3, // {@LIM3|nom|max|min}
+    Lim2, // {@LIM2|max|min}, two-sided without a nominal
+    None, // just the measured value
+}
+
+impl LimitFormat {
+    pub const NAMES: [&'static str; 3] = ["lim3", "lim2", "none"];
+
+    // Names used in the test definition files
+    pub fn from_name(name: &str) -> Option<LimitFormat> {
+        match name.to_lowercase().as_str() {
+            "lim3" => Some(LimitFormat::Lim3),
+            "lim2" => Some(LimitFormat::Lim2),
+            "none" => Some(LimitFormat::None),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            LimitFormat::Lim3 => "lim3",
+            LimitFormat::Lim2 => "lim2",
+            LimitFormat::None => "none",
+        }
+    }
+}
+
+// A measurement line of an analog test, with its own limits and unit.
+// Besides the ones of the type, a test can have extra ones, like the dissipation factor
+// measured together with the capacitance in-situ.
+#[derive(Clone, PartialEq)]
+pub struct Measurement {
+    pub unit: String,
+    pub min: f32,
+    pub nom: f32,
+    pub max: f32,
+}
+
+// Generator settings of the measurements, the same for every test
+#[derive(Clone, Copy)]
+pub struct Sampling {
+    pub high_fail_ratio: u8, // 0-100%, how many of the failing measurements are above max
+    pub fail_margin: f32,    // 0-100%, of the limit, failing measurements are at least this far out
+    pub resolution: Option<Resolution>,
+    pub temperature_delta: f32, // °C, from the 25°C reference of the limits
+}
+
+impl Test {
+    // generates a random measurement between a the limits if is_ok (normal distribution).
+    // A marginal one is just inside the limits, in the outer MARGINAL_BAND of the window.
+    // If not, then it will be between 0 and min, or between max and 2*max,
+    // moved out by the fail margin. high_fail_ratio (0-100%) sets how often the latter is choosen.
+    // Failing resistors are open or shorted instead, with high_fail_ratio as the open ones.
+    // Then it's shifted by the temperature coefficient, and the drift is added, for the hours
+    // elapsed since the start. These are not clamped, so they can push the measurement out of
+    // the limits.
+    // At last it is snapped to the resolution, if there is one.
+    pub fn get_measurement(
+        &self,
+        rng: &mut StdRng,
+        is_ok: bool,
+        marginal: bool,
+        hours: f32,
+        sampling: &Sampling,
+    ) -> f32 {
+        let value = match self.ttype.get_limits() {
+            Some(limits) => {
+                let resistor = matches!(self.ttype, TType::Resistor(..));
+                sample(rng, limits, is_ok, marginal, resistor, sampling)
+            }
+            None => 0.0,
+        };
+
+        let value = value * (1.0 + self.get_tempco() * 1e-6 * sampling.temperature_delta);
+        let value = value + self.drift.unwrap_or(0.0) * hours;
+        match (self.ttype.get_limits(), sampling.resolution) {
+            (Some((min, nom, max)), Some(resolution)) => {
+                quantize(value, resolution.get_step(nom), min, max)
+            }
+            _ => value,
+        }
+    }
+
+    // Transistors are logged with two junction measurements (BE and BC) in one block,
+    // every other type has a single measurement. The extra ones come after them, they
+    // pass or fail together with the others, but have no drift and tempco.
+    pub fn get_measurements(
+        &self,
+        rng: &mut StdRng,
+        is_ok: bool,
+        marginal: bool,
+        hours: f32,
+        sampling: &Sampling,
+    ) -> Vec<f32> {
+        let mut ret = vec![self.get_measurement(rng, is_ok, marginal, hours, sampling)];
+        if let TType::Transistor(..) = self.ttype {
+            ret.push(self.get_measurement(rng, is_ok, marginal, hours, sampling));
+        }
+        if self.ttype.get_limits().is_some() {
+            for m in &self.extra {
+                let limits = (m.min, m.nom, m.max);
+                let value = sample(rng, limits, is_ok, marginal, false, sampling);
+                ret.push(match sampling.resolution {
+                    Some(resolution) => quantize(value, resolution.get_step(m.nom), m.min, m.max),
+                    None => value,
+                });
+            }
+        }
+        ret
+    }
+
+    // Every measurement line of the block, empty for pass/fail only tests
+    pub fn get_specs(&self) -> Vec<Measurement> {
+        let Some((min, nom, max)) = self.ttype.get_limits() else {
+            return Vec::new();
+        };
+
+        let own = Measurement {
+            unit: self.ttype.get_unit().unwrap_or_default().to_string(),
+            min,
+            nom,
+            max,
+        };
+        let mut ret = match self.ttype {
+            TType::Transistor(..) => vec![own.clone(), own],
+            _ => vec![own],
+        };
+        ret.extend(self.extra.iter().cloned());
+        ret
+    }
+
+    // None for pass/fail only tests
+    pub fn in_limits(&self, measured: &[f32]) -> Option<bool> {
+        self.ttype.get_limits()?;
+        Some(
+            self.get_specs()
+                .iter()
+                .zip(measured)
+                .all(|(spec, m)| (spec.min..=spec.max).contains(m)),
+        )
+    }
+
+    pub fn get_tempco(&self) -> f32 {
+        self.tempco
+            .or(self.ttype.default_tempco())
+            .unwrap_or_default()
+    }
+
+    // The own fail rate, or the global yield
+    pub fn get_yield(&self, test_yield: f32) -> f32 {
+        match self.fail_rate {
+            Some(rate) => 100.0 - rate as f32,
+            None => test_yield,
+        }
+    }
+
+    // For the JSON test definition files
+    pub fn to_definition(&self) -> TestDefinition {
+        let limits = self.ttype.get_limits();
+        TestDefinition {
+            name: self.name.clone(),
+            ttype: self.ttype.get_name().to_string(),
+            min: limits.map(|l| l.0),
+            nom: limits.map(|l| l.1),
+            max: limits.map(|l| l.2),
+            fail_rate: self.fail_rate,
+            drift: self.drift,
+            tempco: self.tempco,
+            limits: self.limit_format.get_name().to_string(),
+            extra: self
+                .extra
+                .iter()
+                .map(|m| ExtraDefinition {
+                    unit: m.unit.clone(),
+                    min: m.min,
+                    nom: m.nom,
+                    max: m.max,
+                })
+                .collect(),
+        }
+    }
+
+    // Inverse of to_definition
+    pub fn from_definition(def: TestDefinition) -> Result<Test, String> {
+        let limit = |value: Option<f32>| value.unwrap_or_default();
+        let ttype = TType::from_name(&def.ttype, limit(def.min), limit(def.nom), limit(def.max))
+            .ok_or_else(|| format!("unknown test type \"{}\"", def.ttype))?;
+        let limit_format = match def.limits.as_str() {
+            "" => LimitFormat::Lim3,
+            name => LimitFormat::from_name(name)
+                .ok_or_else(|| format!("invalid limit format \"{name}\""))?,
+        };
+        if def.fail_rate.is_some_and(|rate| rate > 100) {
+            return Err(format!("invalid fail rate {:?}", def.fail_rate));
+        }
+
+        Ok(Test {
+            name: def.name,
+            ttype,
+            fail_rate: def.fail_rate,
+            drift: def.drift,
+            tempco: def.tempco,
+            extra: def
+                .extra
+                .into_iter()
+                .map(|m| Measurement {
+                    unit: m.unit,
+                    min: m.min,
+                    nom: m.nom,
+                    max: m.max,
+                })
+                .collect(),
+            limit_format,
+        })
+    }
+
+    // Measurements exactly at nominal, for golden boards
+    pub fn get_nominals(&self) -> Vec<f32> {
+        self.get_specs().iter().map(|spec| spec.nom).collect()
+    }
+}
+
+// A measurement between the limits if is_ok, see Test::get_measurement
+fn sample(
+    rng: &mut StdRng,
+    (min, nom, max): (f32, f32, f32),
+    is_ok: bool,
+    marginal: bool,
+    resistor: bool,
+    sampling: &Sampling,
+) -> f32 {
+    let high_fail_ratio = sampling.high_fail_ratio;
+    // swapped or equal limits (see validate_tests) must not panic here
+    let (min, max) = (min.min(max), min.max(max));
+    let band = (max - min) * MARGINAL_BAND;
+    if is_ok && marginal {
+        if rng.gen_bool(0.5) {
+            gen_range_or(rng, max - band, max)
+        } else {
+            gen_range_or(rng, min, min + band)
+        }
+    } else if is_ok {
+        // normal distribution around nom, with the limits at +-3 sigma
+        Normal::new(nom, (max - min) / 6.0)
+            .map(|normal| normal.sample(rng))
+            .unwrap_or(nom)
+            .clamp(min, max)
+    } else if resistor {
+        // open: far above max, or short: below an ohm, the offset of the tester
+        // can make it slightly negative
+        if rng.gen_range(0..100) < high_fail_ratio {
+            gen_range_or(rng, max * 1e3, max * 1e6)
+        } else {
+            let short = (min * 1e-2).min(1.0);
+            gen_range_or(rng, -short * 0.1, short)
+        }
+    } else if rng.gen_range(0..100) < high_fail_ratio {
+        let margin = max.abs() * sampling.fail_margin / 100.0;
+        gen_range_or(rng, max + margin, max * 2.0 + margin)
+    } else {
+        let margin = min.abs() * sampling.fail_margin / 100.0;
+        gen_range_or(rng, 0.0, min - margin)
+    }
+}
+
+// Snaps the value to the steps of the resolution, but keeps it on the same side of the limits,
+// so a passing measurement doesn't become failing and the other way around.
+fn quantize(value: f32, step: f32, min: f32, max: f32) -> f32 {
+    if step.is_nan() || step <= 0.0 {
+        return value;
+    }
+
+    let inside = (min..=max).contains(&value);
+    let snapped = (value / step).round() * step;
+    if (min..=max).contains(&snapped) == inside {
+        return snapped;
+    }
+
+    // one step back, towards the original value
+    let back = if snapped > value {
+        snapped - step
+    } else {
+        snapped + step
+    };
+    if (min..=max).contains(&back) == inside {
+        back
+    } else {
+        value
+    }
+}
+
+// Most boards failing together from a panel defect
+const MAX_CLUSTER: u8 = 5;
+
+// Part of the limit window at each end, where the measurements of marginal boards are
+const MARGINAL_BAND: f32 = 0.05;
+
+// Random value in from..to, or from if the range is empty
+fn gen_range_or(rng: &mut StdRng, from: f32, to: f32) -> f32 {
+    if from < to {
+        rng.gen_range(from..to)
+    } else {
+        from
+    }
+}
+
+// Checks that min < nom < max for every analog test.
+// The error lists the bad tests, one per line.
+pub fn validate_tests(tests: &[Test]) -> Result<(), String> {
+    let mut errors: Vec<String> = tests
+        .iter()
+        .flat_map(|test| {
+            test.get_specs().into_iter().filter_map(|spec| {
+                let (min, nom, max) = (spec.min, spec.nom, spec.max);
+                (!(min < nom && nom < max)).then(|| {
+                    format!(
+                        "{}: limits are not min < nom < max ({min} {nom} {max})",
+                        test.name
+                    )
+                })
+            })
+        })
+        .collect();
+    errors.dedup(); // the junctions of a transistor share the limits
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+// Nodes reported open by failed pins tests
+pub const NODE_NAMES: [&str; 16] = [
+    "GND", "VCC_3V3", "VCC_5V", "VBAT", "RESET", "CLK", "SDA", "SCL", "TX", "RX", "MOSI", "MISO",
+    "CS", "EN", "ADC0", "PWM1",
+];
+
+// Create dummy tests, as many of each type as in counts (defaults in brackets).
+// 1x pin test
+// [2x] jumper test (pass/fail only)
+// [10x] capacitor test (limits are +- 10-30%)
+// [10x] resistor test (limits are +- 1-5%)
+// [5x] inductor test (limits are +- 10-20%)
+// [5x] diode test (forward voltage, limits are +- 5%)
+// [1x] zener test (breakdown voltage, limits are +- 5%)
+// [1x] LED test (forward voltage, limits are +- 10%)
+// [2x] transistor test (junction drops, limits are +- 10%)
+pub fn populate_tests(rng: &mut StdRng, counts: &TestCounts, ranges: &NominalRanges) -> Vec<Test> {
+    use TType::*;
+    let mut ret: Vec<Test> = vec![Test {
+        name: "pins".to_string(),
+        ttype: Pin,
+        fail_rate: None,
+        drift: None,
+        tempco: None,
+        extra: Vec::new(),
+        limit_format: LimitFormat::Lim3,
+    }];
+
+    for i in 1..=counts.jumpers {
+        ret.push(Test {
+            name: format!("j{i:02.0}"),
+            ttype: Jumper,
+            fail_rate: None,
+            drift: None,
+            tempco: None,
+            extra: Vec::new(),
+            limit_format: LimitFormat::Lim3,
+        })
+    }
+
+    for i in 1..=counts.capacitors {
+        let (from, to) = ranges.capacitors;
+        let nominal = gen_range_or(rng, from, to);
+        let min = nominal * rng.gen_range(0.7..0.9);
+        let max = nominal * rng.gen_range(1.1..1.3);
+        ret.push(Test {
+            name: format!("c{i:02.0}"),
+            ttype: Capacitor(min, nominal, max),
+            fail_rate: None,
+            drift: None,
+            tempco: None,
+            extra: Vec::new(),
+            limit_format: LimitFormat::Lim3,
+        })
+    }
+
+    for i in 1..=counts.resistors {
+        let (from, to) = ranges.resistors;
+        let nominal = gen_range_or(rng, from, to);
+        let min = nominal * rng.gen_range(0.95..0.99);
+        let max = nominal * rng.gen_range(1.01..1.05);
+        ret.push(Test {
+            name: format!("r{i:02.0}"),
+            ttype: Resistor(min, nominal, max),
+            fail_rate: None,
+            drift: None,
+            tempco: None,
+            extra: Vec::new(),
+            limit_format: LimitFormat::Lim3,
+        })
+    }
+
+    for i in 1..=counts.inductors {
+        let nominal: f32 = rng.gen_range(1E-6..1E-1);
+        let min = nominal * rng.gen_range(0.8..0.9);
+        let max = nominal * rng.gen_range(1.1..1.2);
+        ret.push(Test {
+            name: format!("l{i:02.0}"),
+            ttype: Inductor(min, nominal, max),
+            fail_rate: None,
+            drift: None,
+            tempco: None,
+            extra: Vec::new(),
+            limit_format: LimitFormat::Lim3,
+        })
+    }
+
+    for i in 1..=counts.diodes {
+        let nominal: f32 = rng.gen_range(0.3..0.8);
+        ret.push(Test {
+            name: format!("d{i:02.0}"),
+            ttype: Diode(nominal * 0.95, nominal, nominal * 1.05),
+            fail_rate: None,
+            drift: None,
+            tempco: None,
+            extra: Vec::new(),
+            limit_format: LimitFormat::Lim3,
+        })
+    }
+
+    for i in 1..=counts.zeners {
+        let nominal: f32 = rng.gen_range(2.4..15.0);
+        ret.push(Test {
+            name: format!("z{i:02.0}"),
+            ttype: Zener(nominal * 0.95, nominal, nominal * 1.05),
+            fail_rate: None,
+            drift: None,
+            tempco: None,
+            extra: Vec::new(),
+            limit_format: LimitFormat::Lim3,
+        })
+    }
+
+    for i in 1..=counts.leds {
+        let nominal: f32 = rng.gen_range(1.8..3.3);
+        ret.push(Test {
+            name: format!("led{i:02.0}"),
+            ttype: Led(nominal * 0.9, nominal, nominal * 1.1),
+            fail_rate: None,
+            drift: None,
+            tempco: None,
+            extra: Vec::new(),
+            limit_format: LimitFormat::Lim3,
+        })
+    }
+
+    for i in 1..=counts.transistors {
+        let nominal: f32 = rng.gen_range(0.6..0.75);
+        ret.push(Test {
+            name: format!("q{i:02.0}"),
+            ttype: Transistor(nominal * 0.9, nominal, nominal * 1.1),
+            fail_rate: None,
+            drift: None,
+            tempco: None,
+            extra: Vec::new(),
+            limit_format: LimitFormat::Lim3,
+        })
+    }
+
+    ret
+}
+
+// Loads the test definitions from a CSV file, one test per line:
+// name,type,min,nom,max[,fail_rate[,drift[,tempco[,limits[,unit,min,nom,max...]]]]]
+// Limits can be left empty for pin and jumper tests, fail_rate is optional (0-100%).
+// drift is optional, in units per hour, tempco too, in ppm/°C.
+// limits is the LimitFormat in the log, lim3 (default), lim2 or none.
+// Any unit,min,nom,max after them is an extra measurement of the test, see Measurement.
+// Empty lines, lines starting with '#' and a "name,..." header are skipped.
+// A .json file is a list of TestDefinition instead.
+pub fn load_tests(path: &Path) -> io::Result<Vec<Test>> {
+    if is_json(path) {
+        let definitions: Vec<TestDefinition> =
+            serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        return definitions
+            .into_iter()
+            .enumerate()
+            .map(|(i, def)| {
+                Test::from_definition(def).map_err(|e| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("test {}: {e}", i + 1))
+                })
+            })
+            .collect();
+    }
+
+    let invalid = |line: usize, msg: &str| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("line {line}: {msg}"))
+    };
+
+    let mut ret: Vec<Test> = Vec::new();
+
+    for (i, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.to_lowercase().starts_with("name,") {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() < 2 {
+            return Err(invalid(i + 1, "expected name,type,min,nom,max"));
+        }
+
+        let mut limits = [0.0f32; 3];
+        for (limit, field) in limits.iter_mut().zip(fields.iter().skip(2).take(3)) {
+            if !field.is_empty() {
+                *limit = field
+                    .parse()
+                    .map_err(|_| invalid(i + 1, &format!("invalid limit \"{field}\"")))?;
+            }
+        }
+
+        let [min, nom, max] = limits;
+        let ttype = TType::from_name(fields[1], min, nom, max)
+            .ok_or_else(|| invalid(i + 1, &format!("unknown test type \"{}\"", fields[1])))?;
+
+        let fail_rate = match fields.get(5) {
+            Some(field) if !field.is_empty() => Some(
+                field
+                    .parse()
+                    .ok()
+                    .filter(|rate| *rate <= 100)
+                    .ok_or_else(|| invalid(i + 1, &format!("invalid fail rate \"{field}\"")))?,
+            ),
+            _ => None,
+        };
+
+        let drift = match fields.get(6) {
+            Some(field) if !field.is_empty() => Some(
+                field
+                    .parse()
+                    .map_err(|_| invalid(i + 1, &format!("invalid drift \"{field}\"")))?,
+            ),
+            _ => None,
+        };
+
+        let tempco = match fields.get(7) {
+            Some(field) if !field.is_empty() => Some(
+                field
+                    .parse()
+                    .map_err(|_| invalid(i + 1, &format!("invalid tempco \"{field}\"")))?,
+            ),
+            _ => None,
+        };
+
+        let limit_format = match fields.get(8) {
+            Some(field) if !field.is_empty() => LimitFormat::from_name(field)
+                .ok_or_else(|| invalid(i + 1, &format!("invalid limit format \"{field}\"")))?,
+            _ => LimitFormat::Lim3,
+        };
+
+        let mut extra: Vec<Measurement> = Vec::new();
+        for group in fields.get(9..).unwrap_or_default().chunks(4) {
+            let [unit, min, nom, max] = group else {
+                return Err(invalid(i + 1, "expected unit,min,nom,max"));
+            };
+            let limit = |field: &str| {
+                field
+                    .parse()
+                    .map_err(|_| invalid(i + 1, &format!("invalid limit \"{field}\"")))
+            };
+            extra.push(Measurement {
+                unit: unit.to_string(),
+                min: limit(min)?,
+                nom: limit(nom)?,
+                max: limit(max)?,
+            });
+        }
+
+        ret.push(Test {
+            name: fields[0].to_string(),
+            ttype,
+            fail_rate,
+            drift,
+            tempco,
+            extra,
+            limit_format,
+        });
+    }
+
+    Ok(ret)
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+// Inverse of load_tests, CSV or JSON by the extension
+pub fn save_tests(path: &Path, tests: &[Test]) -> io::Result<()> {
+    if is_json(path) {
+        let definitions: Vec<TestDefinition> = tests.iter().map(Test::to_definition).collect();
+        return std::fs::write(path, serde_json::to_string_pretty(&definitions)?);
+    }
+
+    let mut lines: Vec<String> =
+        vec!["name,type,min,nom,max,fail_rate,drift,tempco,limits".to_string()];
+    for test in tests {
+        let limits = match test.ttype.get_limits() {
+            Some((min, nom, max)) => format!("{min:E},{nom:E},{max:E}"),
+            None => ",,".to_string(),
+        };
+        let mut line = format!(
+            "{},{},{},{},{},{},{}",
+            test.name,
+            test.ttype.get_name(),
+            limits,
+            test.fail_rate.map(|r| r.to_string()).unwrap_or_default(),
+            test.drift.map(|d| format!("{d:E}")).unwrap_or_default(),
+            test.tempco.map(|t| t.to_string()).unwrap_or_default(),
+            test.limit_format.get_name()
+        );
+        for m in &test.extra {
+            line += &format!(",{},{:E},{:E},{:E}", m.unit, m.min, m.nom, m.max);
+        }
+        lines.push(line);
+    }
+
+    std::fs::write(path, lines.join("\n") + "\n")
+}
+
+pub struct TResult {
+    pub ok: bool,
+    pub tested: bool,
+    pub measured: Vec<f32>,
+    pub open_nodes: Vec<String>, // of a failed pins test, if Generator::pin_detail is set
+    pub railed: Vec<bool>,       // of the measurements, see Generator::full_scale; empty if none is
+}
+
+// Logged instead of a railed measurement, with RailedValue::Ovld
+pub const OVERLOAD: &str = "OVLD";
+
+// Logfiles use 00 or 0 to sign OK tests, and 01 or 1 for NOK
+impl TResult {
+    // Tests after a failed pins test are not run, and are left out of the log.
+    pub fn not_tested() -> Self {
+        TResult {
+            ok: false,
+            tested: false,
+            measured: Vec::new(),
+            open_nodes: Vec::new(),
+            railed: Vec::new(),
+        }
+    }
+
+    pub fn is_railed(&self, measurement: usize) -> bool {
+        self.railed.get(measurement).copied().unwrap_or(false)
+    }
+
+    pub fn to_short(&self) -> &str {
+        if self.ok {
+            return "0";
+        }
+
+        "1"
+    }
+
+    pub fn to_str(&self) -> &str {
+        if self.ok {
+            return "00";
+        }
+
+        "01"
+    }
+}
+
+pub struct Board {
+    pub DMC: String,
+    pub index: u8,
+    pub results: Vec<TResult>,
+    pub order: Vec<usize>, // indexes of the results, in the order they are logged
+    pub duration: f64,     // in seconds, from the start of the test to its end
+    pub truncated: Option<f64>, // part of the log written, 0.0-1.0, see Generator::corrupt_rate
+}
+
+impl Board {
+    // End of the test, for the timestamps of the logs
+    pub fn end(&self, start: DateTime<Local>) -> DateTime<Local> {
+        start + Duration::milliseconds((self.duration * 1000.0) as i64)
+    }
+
+    // Used only in @BTEST header
+    pub fn get_result(&self) -> &str {
+        for res in &self.results {
+            if !res.ok {
+                return "01";
+            }
+        }
+
+        "00"
+    }
+}
+
+#[derive(Default)]
+pub struct MultiBoard {
+    pub DMC: String,
+    pub btype: usize, // index into Generator::board_types
+    pub boards: Vec<Board>,
+    pub golden: bool, // reference boards for calibration, their files are prefixed with GOLDEN_
+    pub retest: bool, // failed boards tested again, see Generator::queue_retests
+    pub pair: Option<bool>, // passing or failing half of Generator::generate_pair
+}
+
+pub struct Batch {
+    pub target: u32, // value of Generator::exported at the end
+    pub count: u32,
+    pub own_clock: bool, // the virtual clock was started for the batch
+}
+
+// Failed boards waiting to be retested
+pub struct Retest {
+    pub due: DateTime<Local>, // the retest starts then
+    pub multiboard: MultiBoard,
+}
+
+// Board indexes are u8 and start from 1
+pub const MAX_PANELS: u8 = u8::MAX;
+
+// A product: its own tests, panel count and DMC format
+pub struct BoardType {
+    pub name: String,
+    pub panels: u8, // how many pcbs are on a multiboard, 1..=MAX_PANELS
+    // Placeholders: {line}, {year} (2 digits), {doy} (3 digits), {serial} (5 digits), {index}
+    // The serial wraps around after 99999.
+    pub dmc_template: String,
+    // The boards carry the panel's DMC, and differ only in their index
+    pub shared_dmc: bool,
+    pub check_digit: CheckDigit,
+    pub tests: Vec<Test>,
+    pub tests_file: Option<PathBuf>,
+    pub tests_source: String, // file the tests were loaded from, or the error message
+    pub random_tests: TestCounts,
+    pub nominal_ranges: NominalRanges,
+}
+
+impl BoardType {
+    pub fn from_config(config: BoardTypeConfig, rng: &mut StdRng) -> Self {
+        let mut ret = Self {
+            name: config.name,
+            panels: config.panels.max(1),
+            dmc_template: config.dmc_template,
+            shared_dmc: config.shared_dmc,
+            check_digit: config.check_digit,
+            tests: populate_tests(rng, &config.random_tests, &config.nominal_ranges),
+            tests_file: None,
+            tests_source: "random".to_string(),
+            random_tests: config.random_tests,
+            nominal_ranges: config.nominal_ranges,
+        };
+
+        if let Some(path) = config.tests_file {
+            ret.load_tests_from(&path);
+        }
+
+        ret
+    }
+
+    pub fn to_config(&self) -> BoardTypeConfig {
+        BoardTypeConfig {
+            name: self.name.clone(),
+            panels: self.panels,
+            dmc_template: self.dmc_template.clone(),
+            shared_dmc: self.shared_dmc,
+            check_digit: self.check_digit,
+            tests_file: self.tests_file.clone(),
+            random_tests: self.random_tests,
+            nominal_ranges: self.nominal_ranges,
+        }
+    }
+
+    pub fn load_tests_from(&mut self, path: &Path) {
+        match load_tests(path) {
+            Ok(tests) => {
+                self.tests = tests;
+                self.tests_file = Some(path.to_path_buf());
+                self.tests_source = path.display().to_string();
+            }
+            Err(e) => {
+                self.tests_source = format!("ERR: loading {} failed: {e}", path.display());
+            }
+        }
+    }
+}
+
+// Cuts the log before one of its blocks, leaving the ones around it unclosed.
+// kept (0.0-1.0) chooses the block, from the second one.
+pub fn truncate_log(text: &str, kept: f64) -> &str {
+    let cuts: Vec<usize> = text
+        .match_indices('{')
+        .map(|(i, _)| i)
+        .filter(|&i| i > 0)
+        .collect();
+    match cuts.get(((kept * cuts.len() as f64) as usize).min(cuts.len().saturating_sub(1))) {
+        Some(&cut) => &text[..cut],
+        None => text,
+    }
+}
+
+// User text in a log field, without the separators that would break the log
+fn log_field(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, '|' | '{' | '}'))
+        .collect()
+}
+
+// Test time of a board as HHMMSS, like the time of the timestamps.
+// Counted between the whole seconds of the start and end, so it matches them.
+fn log_duration(start: DateTime<Local>, end: DateTime<Local>) -> String {
+    let seconds = (end.timestamp() - start.timestamp()).clamp(0, 99 * 3600 + 59 * 60 + 59);
+    format!(
+        "{:02}{:02}{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+// Flags of the logfiles
+fn yes_no(flag: bool) -> &'static str {
+    if flag {
+        "y"
+    } else {
+        "n"
+    }
+}
+
+// Replaces the "{key}" placeholders in the template with their values
+pub fn fill_template(template: &str, fields: &[(&str, String)]) -> String {
+    let mut ret = template.to_string();
+    for (key, value) in fields {
+        ret = ret.replace(&format!("{{{key}}}"), value);
+    }
+
+    ret
+}
+
+// The serial field of the DMCs has 5 digits, so after 99999 it rolls over to 1 (never 0).
+// Applied to last_id too, so it can't overflow in a long run.
+const MAX_SERIAL: u32 = 99_999;
+
+// Code 39 check character: the sum of the values of the characters, modulo 43.
+// Lowercase letters count as uppercase, characters outside the set are skipped.
+pub fn check_digit_mod43(payload: &str) -> char {
+    const CHARSET: &[u8; 43] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ-. $/+%";
+
+    let sum: usize = payload
+        .bytes()
+        .filter_map(|c| CHARSET.iter().position(|&s| s == c.to_ascii_uppercase()))
+        .sum();
+    CHARSET[sum % 43] as char
+}
+
+// GS1 check digit: the digits are weighted 3 and 1 alternately from the right,
+// and the check digit brings the sum up to a multiple of 10. Other characters are skipped.
+pub fn check_digit_mod10(payload: &str) -> char {
+    let sum: u32 = payload
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, digit)| if i % 2 == 0 { digit * 3 } else { digit })
+        .sum();
+    char::from_digit((10 - sum % 10) % 10, 10).unwrap_or('0')
+}
+
+// The nodes reported by a failed pins test, with pin_detail
+fn roll_open_nodes(rng: &mut StdRng) -> Vec<String> {
+    let count = rng.gen_range(1..=3);
+    NODE_NAMES
+        .choose_multiple(rng, count)
+        .map(|node| node.to_string())
+        .collect()
+}
+
+fn wrap_serial(serial: u32) -> u32 {
+    (serial.max(1) - 1) % MAX_SERIAL + 1
+}
+
+// chance in %, fractions included, so a 99.7% yield is possible
+pub fn should_pass(rng: &mut StdRng, chance: f32) -> bool {
+    rng.gen::<f32>() * 100.0 < chance
+}
+
+// Format of the simulation start time, in the config and in the GUI
+pub const SIM_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// Format of Config::last_id_date
+const ID_DATE_FORMAT: &str = "%Y-%m-%d";
+
+pub fn parse_sim_time(text: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(text.trim(), SIM_TIME_FORMAT).ok()
+}
+
+// Analog values of the logfiles, like +1.234E-9.
+// With a precision they have that many significant digits, otherwise as many as needed.
+pub fn format_value(value: f32, precision: Option<u8>) -> String {
+    match precision {
+        Some(digits) => format!("{:+.*E}", digits.max(1) as usize - 1, value),
+        None => format!("{value:+E}"),
+    }
+}
+
+// The logfiles are built from strings, so this guards their nesting:
+// no block is closed before it's opened, and every one is closed at the end.
+pub fn validate_braces(text: &str) -> bool {
+    let mut depth: usize = 0;
+    for c in text.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return false,
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth == 0
+}
+
+// Uses the seed if there is one, so runs can be reproduced
+pub fn make_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+// Everything needed to generate and save the logfiles, without the GUI.
+// Used by both the GUI and the headless mode.
+pub struct Generator {
+    pub output_dir: PathBuf,
+
+    pub test_yield: f32,     //0-100%
+    pub high_fail_ratio: u8, // 0-100%, how many of the failing measurements are above max
+    pub marginal_rate: u8,   // 0-100%, chance of a board measuring close to the limits
+    pub cluster_rate: u8,    // 0-100%, chance of a multiboard having a panel defect
+    pub corrupt_rate: u8,    // 0-100%, chance of a board's log being cut off, for testing parsers
+    pub fail_margin: f32,    // see Sampling
+    pub temperature_cycle: Option<TemperatureCycle>,
+    pub resolution: Option<Resolution>, // of the analog measurements
+    pub testing_time: f64,              // in seconds, nominal
+    pub testing_jitter: f64,            // 0-100%, random variation of the testing time
+    pub next_interval: f64,             // in seconds, testing time of the current cycle
+    pub burst: u32,                     // see generate_burst
+    pub board_time_min: f64,            // in seconds, test time of a board in its log
+    pub board_time_max: f64,
+    pub board_delay: f64, // in seconds, between the starts of the boards of a multiboard
+
+    pub last_export: DateTime<Local>, // real or virtual time, see sim_start
+    pub last_cycle: f64,              // in seconds, testing time of the last cycle
+
+    // The pacing counts only the running time, so a pause doesn't make the next cycle due
+    // right away, it continues where it stopped.
+    pub cycle_time: f64, // in seconds, running time of the current cycle before the last pause
+    pub resumed: Option<Instant>, // since when it runs, None while paused
+
+    // With a start time set, the timestamps come from a virtual clock instead of Local::now().
+    // It starts at sim_start and advances by the testing time with each cycle,
+    // while the cycles are still paced in real time.
+    pub sim_start: Option<NaiveDateTime>,
+    pub schedule: Option<Schedule>,
+    pub run_start: DateTime<Local>, // the drift is counted from here
+
+    pub seed: Option<u64>,
+    pub rng: StdRng,
+
+    pub line_id: String,
+    pub station_id: String, // tester ID, in @BATCH and in the filenames with {station}
+    pub btest: BTestFields,
+    pub operator: String, // header lines of the logs, skipped if empty
+    pub fixture_id: String,
+    pub sw_rev: String,
+    // Placeholders: {index}, {time}, {counter} (6 digits, steps with every file), {station}
+    pub filename_template: String,
+    pub extension: String, // of the ICT logfiles, without the dot; empty for none
+    pub daily_file: bool,  // append the ICT logs to one file per day, instead of a file per board
+    pub precision: Option<u8>, // significant digits of the analog values in the logfiles
+    pub file_counter: Cell<u32>,
+    pub summary: bool,        // write summary.csv
+    pub csv_export: bool,     // write a <DMC>.csv next to each logfile
+    pub iso_timestamps: bool, // ISO 8601 in the JSON and summary.csv exports
+    pub output_format: OutputFormat,
+    pub line_ending: LineEnding, // of the ICT logs
+    pub index_scheme: IndexScheme,
+    pub trailing_newline: bool, // after the closing brace of the ICT logs
+    pub subfolders: Subfolders,
+
+    // Saved with the config, so a restart doesn't reissue the serials of the day
+    pub last_id: u32,
+    pub last_id_date: Option<NaiveDate>, // of the clock, see roll_last_id
+    pub exported: u32,                   // boards exported since start
+
+    pub board_types: Vec<BoardType>, // never empty
+    pub type_order: TypeOrder,
+    pub next_type: usize, // used by TypeOrder::Cycle
+    pub test_order: TestOrder,
+
+    pub multiboard: MultiBoard,
+    pub golden_next: bool, // the next saved multiboard is a golden one, cleared after it
+    pub forced_failures: Vec<ForcedFailure>,
+    pub single_defect: bool, // a failing board fails exactly one test
+    pub full_scale: Vec<FullScale>,
+    pub railed_value: RailedValue,
+    forced_pass: bool, // every test passes, with the usual measurements, see generate_pair
+    pub pin_detail: bool, // failed pins tests list some open nodes
+
+    pub retest_rate: u8,   // 0-100%, chance of a failed board getting retested
+    pub retest_delay: f64, // in seconds
+    pub retests: Vec<Retest>,
+
+    pub remote_port: Option<u16>, // see server::Server, started by the GUI
+
+    pub batch: Option<Batch>, // see start_batch
+
+    pub writer: Writer, // all the files are written through this
+}
+
+impl Generator {
+    pub fn its_time(&self) -> bool {
+        self.running_time() > self.next_interval
+    }
+
+    // In seconds, the time the current cycle has been running, without the pauses
+    fn running_time(&self) -> f64 {
+        let running = self.resumed.map(|t| t.elapsed().as_secs_f64());
+        self.cycle_time + running.unwrap_or_default()
+    }
+
+    // Testing time of the cycle from start to now, in seconds, for the stats.
+    // In real time it's the running time, so the pauses don't show up as long cycles.
+    fn cycle_seconds(&self, start: DateTime<Local>, now: DateTime<Local>) -> f64 {
+        match self.sim_start {
+            Some(_) => (now - start).num_milliseconds() as f64 / 1000.0,
+            None => self.running_time(),
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused {
+            if let Some(resumed) = self.resumed.take() {
+                self.cycle_time += resumed.elapsed().as_secs_f64();
+            }
+        } else if self.resumed.is_none() {
+            self.resumed = Some(Instant::now());
+        }
+    }
+
+    // Simulated ambient temperature in °C, cycling around 25°C over the run
+    pub fn temperature(&self, now: DateTime<Local>) -> f32 {
+        let hours = (now - self.run_start).num_milliseconds() as f32 / 3_600_000.0;
+        match self.temperature_cycle {
+            Some(cycle) => 25.0 + cycle.get_offset(hours),
+            None => 25.0,
+        }
+    }
+
+    // Current time of the cycle being generated. The virtual clock follows the schedule.
+    pub fn now(&self) -> DateTime<Local> {
+        match self.sim_start {
+            Some(_) => self.virtual_cycle().1,
+            None => Local::now(),
+        }
+    }
+
+    // Start of the cycle being generated, the last export unless the schedule skipped it
+    pub fn cycle_start(&self) -> DateTime<Local> {
+        match self.sim_start {
+            Some(_) => self.virtual_cycle().0,
+            None => self.last_export,
+        }
+    }
+
+    // A cycle which would end outside the shifts is tested at the start of the next one,
+    // instead of starting in the off-hours.
+    fn virtual_cycle(&self) -> (DateTime<Local>, DateTime<Local>) {
+        let interval = Duration::milliseconds((self.next_interval * 1000.0) as i64);
+        let end = self.last_export + interval;
+        let Some(schedule) = self.schedule else {
+            return (self.last_export, end);
+        };
+
+        let active = Local
+            .from_local_datetime(&schedule.next_active(end.naive_local()))
+            .earliest()
+            .unwrap_or(end);
+        if active == end {
+            (self.last_export, end)
+        } else {
+            (active, active + interval)
+        }
+    }
+
+    // Restarts the virtual clock from the new start time, or switches back to the real time
+    pub fn set_sim_start(&mut self, sim_start: Option<NaiveDateTime>) {
+        self.sim_start = sim_start;
+        self.last_export = match sim_start {
+            Some(start) => Local
+                .from_local_datetime(&start)
+                .earliest()
+                .unwrap_or_else(Local::now),
+            None => Local::now(),
+        };
+        self.run_start = self.last_export;
+    }
+
+    // Randomizes the testing time of the next cycle around the nominal.
+    // The interval never goes below 0.1s, even with 100% jitter.
+    pub fn roll_interval(&mut self) {
+        let jitter = self.testing_jitter.clamp(0.0, 100.0) / 100.0;
+        let factor = if jitter > 0.0 {
+            1.0 + self.rng.gen_range(-jitter..=jitter)
+        } else {
+            1.0
+        };
+        self.next_interval = (self.testing_time * factor).max(0.1);
+    }
+
+    // The boards of a panel are tested one after the other, each board_delay later
+    // than the previous one
+    pub fn board_offset(&self, board: &Board) -> Duration {
+        let delay = self.board_delay.max(0.0) * board.index.saturating_sub(1) as f64;
+        Duration::milliseconds((delay * 1000.0) as i64)
+    }
+
+    // Test time of a board, written into its log. Uniform between board_time_min and max.
+    fn roll_board_time(&mut self) -> f64 {
+        let min = self.board_time_min.max(0.0);
+        let max = self.board_time_max.max(min);
+        self.rng.gen_range(min..=max)
+    }
+
+    // A corrupt log is cut off as if the tester crashed, see truncate_log
+    fn roll_truncation(&mut self) -> Option<f64> {
+        if self.corrupt_rate == 0 || !should_pass(&mut self.rng, self.corrupt_rate.into()) {
+            return None;
+        }
+        Some(self.rng.gen())
+    }
+
+    // Golden boards pass everything, with every measurement at nominal.
+    // DMC and index are only used to find the forced failures of the board.
+    // The test of a panel defect fails too, see choose_panel_defect.
+    pub fn generate_results(
+        &mut self,
+        btype: usize,
+        golden: bool,
+        now: DateTime<Local>,
+        DMC: &str,
+        index: u8,
+        panel_defect: Option<usize>,
+    ) -> Vec<TResult> {
+        let mut ret: Vec<TResult> = Vec::new();
+
+        if golden {
+            for test in &self.board_types[btype].tests {
+                ret.push(TResult {
+                    ok: true,
+                    tested: true,
+                    measured: test.get_nominals(),
+                    open_nodes: Vec::new(),
+                    railed: Vec::new(),
+                });
+            }
+            return ret;
+        }
+
+        let hours = (now - self.run_start).num_milliseconds() as f32 / 3_600_000.0;
+        let culprit = if self.single_defect && !self.forced_pass {
+            self.choose_culprit(btype)
+        } else {
+            None
+        };
+        let sampling = self.sampling(now);
+        // no random draw without marginal boards, so the seeded runs stay the same
+        let marginal =
+            self.marginal_rate > 0 && should_pass(&mut self.rng, self.marginal_rate.into());
+
+        // If the pins test fails, then the tester aborts, the rest is not tested.
+        let mut aborted = false;
+        for (i, test) in self.board_types[btype].tests.iter().enumerate() {
+            if aborted {
+                ret.push(TResult::not_tested());
+                continue;
+            }
+
+            let forced = panel_defect == Some(i)
+                || self
+                    .forced_failures
+                    .iter()
+                    .any(|forced| forced.test == test.name && forced.matches(DMC, index));
+            let mut is_ok = self.forced_pass
+                || (!forced
+                    && if self.single_defect {
+                        culprit != Some(i)
+                    } else {
+                        should_pass(&mut self.rng, test.get_yield(self.test_yield))
+                    });
+            let mut measured =
+                test.get_measurements(&mut self.rng, is_ok, marginal, hours, &sampling);
+            let railed = self.rail(test, &mut measured);
+            // with drift (or temperature) the measurements decide, they can drift out of
+            // (or into) the limits
+            let shifted =
+                test.drift.is_some() || test.get_tempco() * sampling.temperature_delta != 0.0;
+            if shifted && !forced && !self.forced_pass {
+                is_ok = test.in_limits(&measured).unwrap_or(is_ok);
+            }
+
+            let pins_failed = !is_ok && matches!(test.ttype, TType::Pin);
+            let open_nodes = if pins_failed && self.pin_detail {
+                roll_open_nodes(&mut self.rng)
+            } else {
+                Vec::new()
+            };
+
+            ret.push(TResult {
+                ok: is_ok,
+                tested: true,
+                measured,
+                open_nodes,
+                railed,
+            });
+
+            if pins_failed {
+                aborted = true;
+            }
+        }
+
+        ret
+    }
+
+    fn sampling(&self, now: DateTime<Local>) -> Sampling {
+        Sampling {
+            high_fail_ratio: self.high_fail_ratio,
+            fail_margin: self.fail_margin,
+            resolution: self.resolution,
+            temperature_delta: self.temperature(now) - 25.0,
+        }
+    }
+
+    // Clamps the measurements beyond the full scale of their unit, like the tester's range
+    // would. Returns which ones were, empty if none.
+    fn rail(&self, test: &Test, measured: &mut [f32]) -> Vec<bool> {
+        if self.full_scale.is_empty() {
+            return Vec::new();
+        }
+
+        let mut ret = vec![false; measured.len()];
+        for ((value, spec), railed) in measured.iter_mut().zip(test.get_specs()).zip(&mut ret) {
+            let Some(scale) = self.full_scale.iter().find(|scale| scale.unit == spec.unit) else {
+                continue;
+            };
+            let limit = scale.value.abs();
+            if value.abs() > limit {
+                *value = limit.copysign(*value);
+                *railed = true;
+            }
+        }
+
+        if ret.contains(&true) {
+            ret
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn fail_rates(&self, btype: usize) -> Vec<f32> {
+        self.board_types[btype]
+            .tests
+            .iter()
+            .map(|test| (100.0 - test.get_yield(self.test_yield)).clamp(0.0, 100.0))
+            .collect()
+    }
+
+    // Single defect mode: the board fails with the same chance as with independent tests,
+    // but then only one test fails, choosen by the fail rates. None if the board passes.
+    fn choose_culprit(&mut self, btype: usize) -> Option<usize> {
+        let fail_rates = self.fail_rates(btype);
+
+        let pass_chance: f64 = fail_rates
+            .iter()
+            .map(|rate| 1.0 - *rate as f64 / 100.0)
+            .product();
+        if self.rng.gen_bool(pass_chance.clamp(0.0, 1.0)) {
+            return None;
+        }
+
+        // fails only if a rate is not 0, so the weights can't be all zero
+        let weights = rand::distributions::WeightedIndex::new(&fail_rates).ok()?;
+        Some(weights.sample(&mut self.rng))
+    }
+
+    // A test to fail for sure, choosen by the fail rates, or any of them if those are all 0.
+    // None only without tests.
+    fn choose_forced_culprit(&mut self, btype: usize) -> Option<usize> {
+        let fail_rates = self.fail_rates(btype);
+        match rand::distributions::WeightedIndex::new(&fail_rates) {
+            Ok(weights) => Some(weights.sample(&mut self.rng)),
+            Err(_) if fail_rates.is_empty() => None,
+            Err(_) => Some(self.rng.gen_range(0..fail_rates.len())),
+        }
+    }
+
+    pub fn generate_DMC(&self, index: u8, now: DateTime<Local>) -> String {
+        let date: NaiveDate = now.date_naive();
+        let YY = date.year() % 100; // only the last two digits, the line ID is a separate field
+        let DoY = date.ordinal();
+
+        let board_type = &self.board_types[self.multiboard.btype];
+        let DMC = fill_template(
+            &board_type.dmc_template,
+            &[
+                ("line", self.line_id.clone()),
+                ("year", format!("{YY:02.0}")),
+                ("doy", format!("{DoY:03.0}")),
+                (
+                    "serial",
+                    format!("{:05.0}", wrap_serial(self.last_id + index as u32)),
+                ),
+                ("index", format!("{index:02.0}")),
+            ],
+        );
+
+        match board_type.check_digit {
+            CheckDigit::None => DMC,
+            CheckDigit::Mod43 => format!("{DMC}{}", check_digit_mod43(&DMC)),
+            CheckDigit::Mod10 => format!("{DMC}{}", check_digit_mod10(&DMC)),
+        }
+    }
+
+    fn choose_board_type(&mut self) -> usize {
+        match self.type_order {
+            TypeOrder::Cycle => {
+                let ret = self.next_type % self.board_types.len();
+                self.next_type = ret + 1;
+                ret
+            }
+            TypeOrder::Random => self.rng.gen_range(0..self.board_types.len()),
+        }
+    }
+
+    // The serials restart from 1 every day (of the clock, so the simulated one too)
+    fn roll_last_id(&mut self, now: DateTime<Local>) {
+        let today = now.date_naive();
+        if self.last_id_date != Some(today) {
+            self.last_id = 1;
+            self.last_id_date = Some(today);
+        }
+    }
+
+    pub fn generate_multiboard(&mut self, now: DateTime<Local>) {
+        self.roll_last_id(now);
+        self.multiboard.boards.clear();
+
+        let btype = self.choose_board_type();
+        self.multiboard.btype = btype;
+        self.multiboard.golden = self.golden_next;
+        self.multiboard.DMC = self.generate_DMC(0, now);
+        let panel_defect = if self.golden_next {
+            None
+        } else {
+            self.choose_panel_defect(btype)
+        };
+
+        for i in 0..self.board_types[btype].panels {
+            let DMC = if self.board_types[btype].shared_dmc {
+                self.multiboard.DMC.clone()
+            } else {
+                self.generate_DMC(i, now)
+            };
+            let defect = panel_defect
+                .as_ref()
+                .filter(|(_, boards)| boards.contains(&i))
+                .map(|(test, _)| *test);
+            let results = self.generate_results(btype, self.golden_next, now, &DMC, i + 1, defect);
+            let order = self.test_order(btype);
+            let duration = self.roll_board_time();
+            let truncated = self.roll_truncation();
+            self.multiboard.boards.push(Board {
+                DMC,
+                index: i + 1,
+                results,
+                order,
+                duration,
+                truncated,
+            })
+        }
+    }
+
+    // A multiboard only to be looked at: the board type of the next cycle stays the same
+    pub fn generate_preview(&mut self, now: DateTime<Local>) {
+        let next_type = self.next_type;
+        self.generate_multiboard(now);
+        self.next_type = next_type;
+    }
+
+    // A defect of the panel (like a bad solder paste print) fails the same test on a few
+    // neighbouring boards. Returns the test, and the range of the boards (from 0).
+    // Not the pins test, that would just abort the rest.
+    fn choose_panel_defect(&mut self, btype: usize) -> Option<(usize, RangeInclusive<u8>)> {
+        let panels = self.board_types[btype].panels;
+        if self.cluster_rate == 0
+            || panels < 2
+            || !should_pass(&mut self.rng, self.cluster_rate.into())
+        {
+            return None;
+        }
+
+        let tests: Vec<usize> = self.board_types[btype]
+            .tests
+            .iter()
+            .enumerate()
+            .filter(|(_, test)| !matches!(test.ttype, TType::Pin))
+            .map(|(i, _)| i)
+            .collect();
+        let test = *tests.choose(&mut self.rng)?;
+
+        let count = self.rng.gen_range(2..=panels.min(MAX_CLUSTER));
+        let first = self.rng.gen_range(0..=panels - count);
+        Some((test, first..=first + count - 1))
+    }
+
+    // Order of the tests in the log of the next board, as indexes of the tests list
+    fn test_order(&mut self, btype: usize) -> Vec<usize> {
+        let tests = &self.board_types[btype].tests;
+        let mut ret: Vec<usize> = (0..tests.len()).collect();
+
+        match &self.test_order {
+            TestOrder::Defined => {}
+            TestOrder::Shuffled => ret.shuffle(&mut self.rng),
+            TestOrder::Custom(names) => {
+                // stable, so the unlisted ones keep their order
+                ret.sort_by_key(|&i| {
+                    names
+                        .iter()
+                        .position(|name| *name == tests[i].name)
+                        .unwrap_or(names.len())
+                });
+            }
+        }
+
+        ret
+    }
+
+    // Restarts the random sequence, and regenerates the random tests with it.
+    // Tests loaded from files are kept.
+    pub fn reseed(&mut self) {
+        self.rng = make_rng(self.seed);
+        self.next_type = 0;
+        for board_type in &mut self.board_types {
+            if board_type.tests_file.is_none() {
+                board_type.tests = populate_tests(
+                    &mut self.rng,
+                    &board_type.random_tests,
+                    &board_type.nominal_ranges,
+                );
+                board_type.tests_source = "random".to_string();
+            }
+        }
+    }
+
+    // One full cycle: generate a new multiboard, queue it for saving and step the fields.
+    // Returns the names of the logfiles, relative to output_dir.
+    // The files are written in the background, so the returned error is from an earlier write.
+    pub fn generate_and_save(&mut self) -> io::Result<Vec<String>> {
+        let (start, now) = (self.cycle_start(), self.now());
+        let filenames = self.cycle(start, now, self.cycle_seconds(start, now));
+        self.writer.take_error().map(|_| filenames)
+    }
+
+    // Burst mode, for stations which write their logs only once in a while: burst multiboards
+    // in one go, calling on_cycle after each. They split the time since the last export,
+    // each one starting where the previous ended, with the serials stepped as usual.
+    pub fn generate_burst(
+        &mut self,
+        mut on_cycle: impl FnMut(&Generator),
+    ) -> io::Result<Vec<String>> {
+        let burst = self.burst.max(1);
+        let (start, end) = (self.cycle_start(), self.now());
+        let step = (end - start) / burst as i32;
+        let seconds = self.cycle_seconds(start, end) / burst as f64;
+
+        let mut filenames: Vec<String> = Vec::new();
+        for i in 1..=burst {
+            let now = if i == burst {
+                end
+            } else {
+                start + step * i as i32
+            };
+            let from = if i == 1 { start } else { self.last_export };
+            filenames.extend(self.cycle(from, now, seconds));
+            on_cycle(self);
+        }
+        self.writer.take_error().map(|_| filenames)
+    }
+
+    fn cycle(&mut self, start: DateTime<Local>, now: DateTime<Local>, seconds: f64) -> Vec<String> {
+        self.generate_multiboard(now);
+
+        let filenames = self.save_results(&self.multiboard, start, now);
+        self.write_summary(&self.multiboard, now, &filenames);
+
+        self.queue_retests(now);
+        self.last_cycle = seconds;
+        self.update_fields(now);
+        self.golden_next = false;
+        filenames
+    }
+
+    // Batch mode: generates at least count boards right away, without the real time pacing.
+    // The timestamps come from the virtual clock, spaced by the testing time. Without a
+    // simulated start time the clock starts at the last export, and runs only for the batch.
+    // The boards are generated by batch_step, a bit at a time.
+    pub fn start_batch(&mut self, count: u32) {
+        let own_clock = self.sim_start.is_none();
+        if own_clock {
+            self.sim_start = Some(self.last_export.naive_local());
+        }
+
+        self.batch = Some(Batch {
+            target: self.exported + count,
+            count,
+            own_clock,
+        });
+    }
+
+    // Generates multiboards of the batch until the time budget is used up,
+    // calling on_cycle after each. Stops the batch when it's done, or at the first error.
+    // Returns the names of the files written in this step.
+    pub fn batch_step(
+        &mut self,
+        budget: std::time::Duration,
+        mut on_cycle: impl FnMut(&Generator),
+    ) -> io::Result<Vec<String>> {
+        let mut filenames: Vec<String> = Vec::new();
+        let started = Instant::now();
+        while let Some(batch) = &self.batch {
+            if self.exported >= batch.target {
+                self.stop_batch();
+                break;
+            }
+            if started.elapsed() > budget {
+                break;
+            }
+
+            self.save_retests(false);
+            let ret = self.generate_and_save();
+            on_cycle(self);
+            match ret {
+                Ok(names) => filenames.extend(names),
+                Err(e) => {
+                    self.stop_batch();
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(filenames)
+    }
+
+    // Done part of the batch, 0.0-1.0
+    pub fn batch_progress(&self) -> Option<f32> {
+        self.batch.as_ref().map(|batch| {
+            let left = batch.target.saturating_sub(self.exported);
+            1.0 - left as f32 / batch.count.max(1) as f32
+        })
+    }
+
+    // The current multiboard and the queued retests refer to the types by index,
+    // so the ones of the removed type are dropped, and the later ones shifted.
+    pub fn remove_board_type(&mut self, btype: usize) {
+        if btype >= self.board_types.len() {
+            return;
+        }
+
+        self.board_types.remove(btype);
+        self.multiboard = MultiBoard::default();
+        self.retests
+            .retain(|retest| retest.multiboard.btype != btype);
+        for retest in &mut self.retests {
+            if retest.multiboard.btype > btype {
+                retest.multiboard.btype -= 1;
+            }
+        }
+        if self.next_type > btype {
+            self.next_type -= 1;
+        }
+    }
+
+    // Also used for cancelling. The files generated so far are still written.
+    pub fn stop_batch(&mut self) {
+        // back to the real time, but the drift keeps counting from the same start
+        if self.batch.take().is_some_and(|batch| batch.own_clock) {
+            self.sim_start = None;
+            self.last_export = Local::now();
+        }
+    }
+
+    // The same multiboard twice, for diffing the logs: once passing everything, and once with
+    // one test failing on each board. The rest of the results are the same in both.
+    // Saved as one cycle, the files are prefixed with PASS_ and FAIL_. Returns their names.
+    pub fn generate_pair(&mut self) -> io::Result<Vec<String>> {
+        let (start, now) = (self.cycle_start(), self.now());
+        let golden = std::mem::take(&mut self.golden_next);
+        self.forced_pass = true;
+        self.generate_multiboard(now);
+        self.forced_pass = false;
+        self.golden_next = golden;
+
+        self.multiboard.pair = Some(true);
+        let mut filenames = self.save_results(&self.multiboard, start, now);
+        self.write_summary(&self.multiboard, now, &filenames);
+
+        let btype = self.multiboard.btype;
+        let hours = (now - self.run_start).num_milliseconds() as f32 / 3_600_000.0;
+        let sampling = self.sampling(now);
+        for i in 0..self.multiboard.boards.len() {
+            let Some(culprit) = self.choose_forced_culprit(btype) else {
+                continue;
+            };
+            let test = &self.board_types[btype].tests[culprit];
+            let mut measured = test.get_measurements(&mut self.rng, false, false, hours, &sampling);
+            let railed = self.rail(test, &mut measured);
+            // the tester aborts after the pins test, like in generate_results
+            let pins = matches!(test.ttype, TType::Pin);
+            let open_nodes = if pins && self.pin_detail {
+                roll_open_nodes(&mut self.rng)
+            } else {
+                Vec::new()
+            };
+
+            let results = &mut self.multiboard.boards[i].results;
+            results[culprit] = TResult {
+                ok: false,
+                tested: true,
+                measured,
+                open_nodes,
+                railed,
+            };
+            if pins {
+                results[culprit + 1..].fill_with(TResult::not_tested);
+            }
+        }
+
+        self.multiboard.pair = Some(false);
+        let failing = self.save_results(&self.multiboard, start, now);
+        self.write_summary(&self.multiboard, now, &failing);
+        filenames.extend(failing);
+
+        self.multiboard.pair = None;
+        self.last_cycle = self.cycle_seconds(start, now);
+        self.update_fields(now);
+        self.writer.take_error().map(|_| filenames)
+    }
+
+    // Failed boards are retested with a chance of retest_rate, after retest_delay.
+    // The retest gets new results, but keeps the DMC and index.
+    fn queue_retests(&mut self, now: DateTime<Local>) {
+        let btype = self.multiboard.btype;
+        let mut boards: Vec<Board> = Vec::new();
+
+        for i in 0..self.multiboard.boards.len() {
+            let board = &self.multiboard.boards[i];
+            if board.get_result() == "00" || !should_pass(&mut self.rng, self.retest_rate.into()) {
+                continue;
+            }
+
+            let (DMC, index) = (board.DMC.clone(), board.index);
+            let results = self.generate_results(btype, false, now, &DMC, index, None);
+            let order = self.test_order(btype);
+            boards.push(Board {
+                DMC,
+                index,
+                results,
+                order,
+                duration: self.roll_board_time(),
+                truncated: self.roll_truncation(),
+            });
+        }
+
+        if !boards.is_empty() {
+            self.retests.push(Retest {
+                due: now + Duration::milliseconds((self.retest_delay * 1000.0) as i64),
+                multiboard: MultiBoard {
+                    DMC: self.multiboard.DMC.clone(),
+                    btype,
+                    boards,
+                    golden: false,
+                    retest: true,
+                    pair: None,
+                },
+            });
+        }
+    }
+
+    // Saves the retests which are due, or all of them if force is set.
+    pub fn save_retests(&mut self, force: bool) {
+        let now = self.now();
+        let (due, pending): (Vec<Retest>, Vec<Retest>) = std::mem::take(&mut self.retests)
+            .into_iter()
+            .partition(|retest| force || retest.due <= now);
+        self.retests = pending;
+
+        // forced ones can be saved before they are due
+        for retest in due {
+            let start = retest.due.min(now);
+            let filenames = self.save_results(&retest.multiboard, start, now);
+            self.write_summary(&retest.multiboard, now, &filenames);
+        }
+    }
+
+    pub fn update_fields(&mut self, now: DateTime<Local>) {
+        self.last_export = now;
+        self.cycle_time = 0.0;
+        if self.resumed.is_some() {
+            self.resumed = Some(Instant::now());
+        }
+        self.last_id = wrap_serial(self.last_id + self.multiboard.boards.len() as u32);
+        self.exported += self.multiboard.boards.len() as u32;
+        self.roll_interval();
+    }
+
+    // Every call steps the file counter, so with {counter} in the template the names are unique,
+    // even if several multiboards are saved in the same second.
+    pub fn generate_filename(&self, time_now: DateTime<Local>, index: u8) -> String {
+        let counter = self.file_counter.get();
+        self.file_counter.set(counter.wrapping_add(1));
+
+        fill_template(
+            &self.filename_template,
+            &[
+                ("index", self.index_scheme.format(index)),
+                ("time", format!("{}", time_now.format("%y%m%d%H%M%S"))),
+                ("counter", format!("{counter:06.0}")),
+                ("station", log_field(&self.station_id)),
+            ],
+        )
+    }
+
+    // Timestamps:
+    // @BATCH - start of the batch, which is the start of the multiboard's test, shared by
+    //          its boards
+    // @BTEST - start and end of the board's test, from its board_offset after the batch start
+    //          and its duration
+    // A multiboard (or the retested boards of one) is a batch, the last field of @BATCH
+    // is its board count. Every block is closed on its own line, @BATCH with the last one.
+    pub fn generate_log(
+        &self,
+        multiboard: &MultiBoard,
+        board: &Board,
+        start: DateTime<Local>,
+    ) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        let board_start = start + self.board_offset(board);
+        let end = board.end(board_start);
+
+        // Optional metadata, each on its own line before @BATCH
+        for (tag, value) in [
+            ("OPERATOR", &self.operator),
+            ("FIXTURE", &self.fixture_id),
+            ("SW_REV", &self.sw_rev),
+        ] {
+            let value = log_field(value);
+            if !value.is_empty() {
+                lines.push(format!("{{@{tag}|{value}}}"));
+            }
+        }
+
+        let station = log_field(&self.station_id);
+        lines.push(format!(
+            "{{@BATCH|DUMMY||{}|1||btest|{}||i30704CE{}BZ01|DUMMY|RevA|DUMMY||D|{}",
+            station,
+            start.format("%y%m%d%H%M%S"),
+            station,
+            multiboard.boards.len()
+        ));
+        lines.push(format!(
+            "{{@BTEST|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{:02.0}|{}",
+            board.DMC,
+            board.get_result(),
+            board_start.format("%y%m%d%H%M%S"),
+            log_duration(board_start, end),
+            if multiboard.retest { "1" } else { "0" },
+            log_field(&self.btest.log_level),
+            log_field(&self.btest.log_set),
+            yes_no(self.btest.learning),
+            yes_no(self.btest.known_good || multiboard.golden),
+            end.format("%y%m%d%H%M%S"),
+            log_field(&self.btest.status_qualifier),
+            board.index,
+            multiboard.DMC
+        ));
+
+        let position = self.index_scheme.format(board.index);
+        let tests = &self.board_types[multiboard.btype].tests;
+        // the tests could have been edited since, so the indexes are checked
+        for (test, result) in board
+            .order
+            .iter()
+            .filter_map(|&i| Some((tests.get(i)?, board.results.get(i)?)))
+        {
+            if !result.tested {
+                continue;
+            }
+
+            match test.ttype {
+                // the last field is the number of open pins, listed under it
+                TType::Pin => {
+                    lines.push(format!(
+                        "{{@PF|{}%pins|{}|{}",
+                        position,
+                        result.to_short(),
+                        result.open_nodes.len()
+                    ));
+                    for node in &result.open_nodes {
+                        lines.push(format!("{{@PIN|{node}}}"));
+                    }
+                    lines.push("}".to_string());
+                }
+                TType::Jumper => {
+                    lines.push(format!(
+                        "{{@BLOCK|{}%{}|{}",
+                        position,
+                        test.name,
+                        result.to_str()
+                    ));
+                    lines.push(format!(
+                        "{{@{}|{}}}",
+                        test.ttype.get_tag(),
+                        result.to_short()
+                    ));
+                    lines.push("}".to_string());
+                }
+                TType::Capacitor(..)
+                | TType::Resistor(..)
+                | TType::Diode(..)
+                | TType::Zener(..)
+                | TType::Led(..)
+                | TType::Inductor(..)
+                | TType::Transistor(..) => {
+                    lines.push(format!(
+                        "{{@BLOCK|{}%{}|{}",
+                        position,
+                        test.name,
+                        result.to_str()
+                    ));
+                    let value = |v: f32| format_value(v, self.precision);
+                    for (i, (measured, spec)) in
+                        result.measured.iter().zip(test.get_specs()).enumerate()
+                    {
+                        let measured =
+                            if result.is_railed(i) && self.railed_value == RailedValue::Ovld {
+                                OVERLOAD.to_string()
+                            } else {
+                                value(*measured)
+                            };
+                        let limits = match test.limit_format {
+                            LimitFormat::Lim3 => format!(
+                                "{{@LIM3|{}|{}|{}}}",
+                                value(spec.nom),
+                                value(spec.max),
+                                value(spec.min)
+                            ),
+                            LimitFormat::Lim2 => {
+                                format!("{{@LIM2|{}|{}}}", value(spec.max), value(spec.min))
+                            }
+                            LimitFormat::None => String::new(),
+                        };
+                        lines.push(format!(
+                            "{{@{}|{}|{}|{}{limits}}}",
+                            test.ttype.get_tag(),
+                            result.to_short(),
+                            measured,
+                            spec.unit
+                        ));
+                    }
+                    lines.push("}".to_string());
+                }
+            }
+        }
+
+        lines.push("}".to_string()); // @BTEST
+        lines.push("}".to_string()); // @BATCH
+
+        let mut ret = lines.join(self.line_ending.as_str());
+        debug_assert!(validate_braces(&ret), "unbalanced log of {}", board.DMC);
+        if self.trailing_newline {
+            ret += self.line_ending.as_str();
+        }
+        ret
+    }
+
+    // Timestamps of the JSON and summary.csv exports, the ICT logs keep their own format
+    fn format_time(&self, time: DateTime<Local>, format: &str) -> String {
+        if self.iso_timestamps {
+            time.to_rfc3339_opts(SecondsFormat::Secs, false)
+        } else {
+            time.format(format).to_string()
+        }
+    }
+
+    // Flat CSV version of the log, one line per measurement.
+    // Pass/fail only tests have no measurement and limits.
+    pub fn to_csv(&self, multiboard: &MultiBoard, board: &Board) -> String {
+        let mut lines: Vec<String> = vec!["test,measured,min,nom,max,pass".to_string()];
+
+        let tests = &self.board_types[multiboard.btype].tests;
+        for (test, result) in tests.iter().zip(board.results.iter()) {
+            if !result.tested {
+                continue;
+            }
+
+            let pass = if result.ok { "1" } else { "0" };
+            let specs = test.get_specs();
+            if specs.is_empty() {
+                lines.push(format!("{},,,,,{}", test.name, pass));
+            }
+            for (measured, spec) in result.measured.iter().zip(specs) {
+                lines.push(format!(
+                    "{},{:E},{:E},{:E},{:E},{}",
+                    test.name, measured, spec.min, spec.nom, spec.max, pass
+                ));
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    pub fn generate_json(
+        &self,
+        multiboard: &MultiBoard,
+        board: &Board,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> String {
+        let pass_fail = |ok: bool| if ok { "PASS" } else { "FAIL" };
+
+        let tests = &self.board_types[multiboard.btype].tests;
+        let record = JsonBoard {
+            dmc: &board.DMC,
+            multiboard_dmc: &multiboard.DMC,
+            index: board.index,
+            result: pass_fail(board.get_result() == "00"),
+            start: self.format_time(start, "%y%m%d%H%M%S"),
+            end: self.format_time(end, "%y%m%d%H%M%S"),
+            tests: tests
+                .iter()
+                .zip(board.results.iter())
+                .filter(|(_, result)| result.tested)
+                .map(|(test, result)| {
+                    let limits = test.ttype.get_limits();
+                    JsonTest {
+                        name: &test.name,
+                        ttype: test.ttype.get_name(),
+                        result: pass_fail(result.ok),
+                        measured: if limits.is_some() {
+                            result.measured.clone()
+                        } else {
+                            Vec::new()
+                        },
+                        unit: test.ttype.get_unit(),
+                        min: limits.map(|l| l.0),
+                        nom: limits.map(|l| l.1),
+                        max: limits.map(|l| l.2),
+                        extra: test
+                            .extra
+                            .iter()
+                            .map(|m| JsonLimits {
+                                unit: &m.unit,
+                                min: m.min,
+                                nom: m.nom,
+                                max: m.max,
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+        };
+
+        serde_json::to_string_pretty(&record).unwrap_or_default()
+    }
+
+    // Log of the first board of the current multiboard
+    pub fn preview(&self) -> Option<String> {
+        self.multiboard
+            .boards
+            .first()
+            .map(|board| self.generate_log(&self.multiboard, board, self.last_export))
+    }
+
+    // The ICT logs of the boards of the current multiboard, tested from the last export
+    pub fn ict_logs(&self) -> Vec<String> {
+        self.multiboard
+            .boards
+            .iter()
+            .map(|board| self.generate_log(&self.multiboard, board, self.last_export))
+            .collect()
+    }
+
+    pub fn save_results(
+        &self,
+        multiboard: &MultiBoard,
+        start: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Vec<String> {
+        let mut filenames: Vec<String> = Vec::new();
+
+        // relative to output_dir, the summary.csv stays on the top
+        let subfolder = match self.subfolders {
+            Subfolders::None => PathBuf::new(),
+            Subfolders::Dmc => PathBuf::from(&multiboard.DMC),
+            Subfolders::Time => PathBuf::from(now.format("%y%m%d%H%M%S").to_string()),
+        };
+        let dir = self.output_dir.join(&subfolder);
+
+        let batch_start = start;
+        for board in &multiboard.boards {
+            let (start, now) = (
+                start + self.board_offset(board),
+                now + self.board_offset(board),
+            );
+            let end = board.end(start);
+            let mut filename = self.generate_filename(now, board.index);
+            if multiboard.golden {
+                filename = format!("GOLDEN_{filename}");
+            }
+            match multiboard.pair {
+                Some(true) => filename = format!("PASS_{filename}"),
+                Some(false) => filename = format!("FAIL_{filename}"),
+                None => {}
+            }
+
+            // JSON files get their own extension after the base name
+            let json_path = dir.join(format!("{filename}.json"));
+
+            let extension = self.extension.trim().trim_start_matches('.');
+            if !extension.is_empty() {
+                filename = format!("{filename}.{extension}");
+            }
+            let mut logname = subfolder.join(&filename);
+
+            if self.output_format != OutputFormat::Json {
+                let mut text = self.generate_log(multiboard, board, batch_start);
+                if let Some(kept) = board.truncated {
+                    text = truncate_log(&text, kept).to_string();
+                    log::info!("truncated log: board {}, {} bytes", board.index, text.len());
+                }
+                let path = if self.daily_file {
+                    // named after the day of the test's end, so a run over midnight splits
+                    let mut name = end.format("%Y-%m-%d").to_string();
+                    if !extension.is_empty() {
+                        name = format!("{name}.{extension}");
+                    }
+                    logname = PathBuf::from(&name);
+                    self.output_dir.join(&name)
+                } else {
+                    dir.join(&filename)
+                };
+
+                log::info!(
+                    "new log: board {}, result {}, path {path:?}",
+                    board.index,
+                    board.get_result()
+                );
+                if self.daily_file {
+                    // an empty line between the logs
+                    let newline = self.line_ending.as_str();
+                    let separator = if self.trailing_newline {
+                        newline.to_string()
+                    } else {
+                        newline.repeat(2)
+                    };
+                    self.writer
+                        .append(path, String::new(), format!("{text}{separator}"));
+                } else {
+                    self.writer.write(path, text);
+                }
+            }
+
+            if self.output_format != OutputFormat::Ict {
+                log::info!(
+                    "new JSON: board {}, result {}, path {json_path:?}",
+                    board.index,
+                    board.get_result()
+                );
+                self.writer
+                    .write(json_path, self.generate_json(multiboard, board, start, end));
+            }
+
+            filenames.push(logname.display().to_string());
+
+            if self.csv_export {
+                // with a shared DMC the index tells the boards apart
+                let path = if self.board_types[multiboard.btype].shared_dmc {
+                    dir.join(format!(
+                        "{}-{}.csv",
+                        board.DMC,
+                        self.index_scheme.format(board.index)
+                    ))
+                } else {
+                    dir.join(format!("{}.csv", board.DMC))
+                };
+                self.writer.write(path, self.to_csv(multiboard, board));
+            }
+        }
+
+        filenames
+    }
+
+    // Appends a line per board of the multiboard to summary.csv in output_dir, if enabled.
+    // Takes the filenames returned by save_results.
+    pub fn write_summary(
+        &self,
+        multiboard: &MultiBoard,
+        now: DateTime<Local>,
+        filenames: &[String],
+    ) {
+        if !self.summary {
+            return;
+        }
+
+        let mut lines = String::new();
+        for (board, filename) in multiboard.boards.iter().zip(filenames) {
+            lines += &format!(
+                "{},{},{},{},{},{}\n",
+                self.format_time(now, "%Y-%m-%d %H:%M:%S"),
+                multiboard.DMC,
+                board.DMC,
+                board.index,
+                if board.get_result() == "00" {
+                    "PASS"
+                } else {
+                    "FAIL"
+                },
+                filename
+            );
+        }
+
+        self.writer.append(
+            self.output_dir.join("summary.csv"),
+            "time,multiboard,board,index,result,filename".to_string(),
+            lines,
+        );
+    }
+
+    pub fn from_config(config: Config) -> Self {
+        let mut rng = make_rng(config.seed);
+
+        // on the first run the default directory doesn't exist yet
+        if config.output_dir == Config::default_output_dir() && !config.dry_run {
+            if let Err(e) = std::fs::create_dir_all(&config.output_dir) {
+                log::error!("creating {:?} failed: {e}", config.output_dir);
+            }
+        }
+
+        let mut board_types: Vec<BoardType> = config
+            .board_types
+            .into_iter()
+            .map(|c| BoardType::from_config(c, &mut rng))
+            .collect();
+        if board_types.is_empty() {
+            board_types.push(BoardType::from_config(BoardTypeConfig::default(), &mut rng));
+        }
+
+        let mut writer = Writer::new();
+        writer.dry_run = config.dry_run;
+        writer.set_retries(config.write_retries);
+
+        let mut ret = Self {
+            output_dir: config.output_dir,
+            test_yield: config.test_yield,
+            high_fail_ratio: config.high_fail_ratio,
+            marginal_rate: config.marginal_rate,
+            cluster_rate: config.cluster_rate,
+            corrupt_rate: config.corrupt_rate,
+            fail_margin: config.fail_margin,
+            temperature_cycle: config.temperature_cycle,
+            resolution: config.resolution,
+            testing_time: config.testing_time,
+            testing_jitter: config.testing_jitter,
+            burst: config.burst,
+            next_interval: config.testing_time,
+            board_time_min: config.board_time_min,
+            board_time_max: config.board_time_max,
+            board_delay: config.board_delay,
+            last_export: Local::now(),
+            last_cycle: 0.0,
+            cycle_time: 0.0,
+            resumed: None,
+            sim_start: None,
+            schedule: config.schedule,
+            run_start: Local::now(),
+            seed: config.seed,
+            rng,
+            line_id: config.line_id,
+            station_id: config.station_id,
+            btest: config.btest,
+            operator: config.operator,
+            fixture_id: config.fixture_id,
+            sw_rev: config.sw_rev,
+            filename_template: config.filename_template,
+            extension: config.extension,
+            daily_file: config.daily_file,
+            precision: config.precision,
+            file_counter: Cell::new(0),
+            summary: config.summary,
+            csv_export: config.csv_export,
+            iso_timestamps: config.iso_timestamps,
+            output_format: config.output_format,
+            line_ending: config.line_ending,
+            index_scheme: config.index_scheme,
+            trailing_newline: config.trailing_newline,
+            subfolders: config.subfolders,
+            last_id: config.last_id,
+            last_id_date: config
+                .last_id_date
+                .and_then(|date| NaiveDate::parse_from_str(&date, ID_DATE_FORMAT).ok()),
+            exported: 0,
+            board_types,
+            type_order: config.type_order,
+            test_order: config.test_order,
+            next_type: 0,
+            multiboard: MultiBoard::default(),
+            golden_next: false,
+            forced_failures: config.forced_failures,
+            single_defect: config.single_defect,
+            full_scale: config.full_scale,
+            railed_value: config.railed_value,
+            forced_pass: false,
+            pin_detail: config.pin_detail,
+            retest_rate: config.retest_rate,
+            retest_delay: config.retest_delay,
+            retests: Vec::new(),
+            remote_port: config.remote_port,
+            batch: None,
+            writer,
+        };
+
+        ret.set_sim_start(config.sim_start.as_deref().and_then(parse_sim_time));
+        ret
+    }
+
+    pub fn to_config(&self) -> Config {
+        Config {
+            output_dir: self.output_dir.clone(),
+            test_yield: self.test_yield,
+            high_fail_ratio: self.high_fail_ratio,
+            marginal_rate: self.marginal_rate,
+            cluster_rate: self.cluster_rate,
+            corrupt_rate: self.corrupt_rate,
+            fail_margin: self.fail_margin,
+            temperature_cycle: self.temperature_cycle,
+            resolution: self.resolution,
+            testing_time: self.testing_time,
+            testing_jitter: self.testing_jitter,
+            burst: self.burst,
+            board_time_min: self.board_time_min,
+            board_time_max: self.board_time_max,
+            board_delay: self.board_delay,
+            seed: self.seed,
+            last_id: self.last_id,
+            last_id_date: self
+                .last_id_date
+                .map(|date| date.format(ID_DATE_FORMAT).to_string()),
+            line_id: self.line_id.clone(),
+            station_id: self.station_id.clone(),
+            btest: self.btest.clone(),
+            operator: self.operator.clone(),
+            fixture_id: self.fixture_id.clone(),
+            sw_rev: self.sw_rev.clone(),
+            filename_template: self.filename_template.clone(),
+            extension: self.extension.clone(),
+            daily_file: self.daily_file,
+            precision: self.precision,
+            summary: self.summary,
+            csv_export: self.csv_export,
+            iso_timestamps: self.iso_timestamps,
+            dry_run: self.writer.dry_run,
+            write_retries: self.writer.retries(),
+            output_format: self.output_format,
+            line_ending: self.line_ending,
+            index_scheme: self.index_scheme,
+            trailing_newline: self.trailing_newline,
+            subfolders: self.subfolders,
+            sim_start: self
+                .sim_start
+                .map(|start| start.format(SIM_TIME_FORMAT).to_string()),
+            schedule: self.schedule,
+            board_types: self.board_types.iter().map(|t| t.to_config()).collect(),
+            type_order: self.type_order,
+            test_order: self.test_order.clone(),
+            retest_rate: self.retest_rate,
+            retest_delay: self.retest_delay,
+            forced_failures: self.forced_failures.clone(),
+            single_defect: self.single_defect,
+            full_scale: self.full_scale.clone(),
+            railed_value: self.railed_value,
+            pin_detail: self.pin_detail,
+            remote_port: self.remote_port,
+            dark_mode: true,      // not a generator setting, the GUI sets it
+            stations: Vec::new(), // same
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Seeded, and writes nothing
+    fn generator() -> Generator {
+        Generator::from_config(Config {
+            seed: Some(1),
+            dry_run: true,
+            ..Default::default()
+        })
+    }
+
+    // Writing into a new directory under the temp dir, on the virtual clock.
+    // Removed by the test.
+    fn writing(name: &str) -> (Generator, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("ict_log_maker_{name}_{}", std::process::id()));
+        let mut generator = generator();
+        generator.output_dir = dir.clone();
+        generator.writer.dry_run = false;
+        generator.summary = false;
+        generator.set_sim_start(Some(time(2024, 2, 3, 10).naive_local()));
+        (generator, dir)
+    }
+
+    fn time(y: i32, m: u32, d: u32, h: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap()
+    }
+
+    // Field i of the first line of the tag, {@TAG being field 0
+    fn field<'a>(log: &'a str, tag: &str, i: usize) -> &'a str {
+        let line = log
+            .lines()
+            .find(|line| line.starts_with(&format!("{{@{tag}|")))
+            .unwrap();
+        line.split('|').nth(i).unwrap()
+    }
+
+    // Passing, so every test is logged
+    fn test(name: &str, ttype: TType) -> Test {
+        Test {
+            name: name.to_string(),
+            ttype,
+            fail_rate: Some(0),
+            drift: None,
+            tempco: None,
+            extra: Vec::new(),
+            limit_format: LimitFormat::Lim3,
+        }
+    }
+
+    fn log_time(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%y%m%d%H%M%S").unwrap()
+    }
+
+    #[test]
+    fn dmc_fields() {
+        let mut generator = generator();
+        generator.line_id = "07".to_string();
+        generator.last_id = 42;
+
+        // L{line}{year}{doy}{serial}TB0001010111
+        let DMC = generator.generate_DMC(1, time(2024, 2, 3, 10));
+        assert_eq!(DMC, "L072403400043TB0001010111");
+        assert_eq!(DMC.len(), 25);
+        assert_eq!(&DMC[1..3], "07");
+        assert_eq!(&DMC[3..5], "24");
+        assert_eq!(&DMC[5..8], "034");
+        assert_eq!(&DMC[8..13], "00043");
+
+        // the year keeps its leading zero
+        assert_eq!(
+            &generator.generate_DMC(1, time(2005, 12, 31, 10))[3..8],
+            "05365"
+        );
+    }
+
+    #[test]
+    fn btest_times() {
+        let mut generator = generator();
+        generator.board_delay = 5.0;
+        let start = time(2024, 2, 3, 10);
+        generator.generate_multiboard(start);
+
+        for board in &generator.multiboard.boards {
+            let log = generator.generate_log(&generator.multiboard, board, start);
+            let batch = log_time(field(&log, "BATCH", 7));
+            let (test_start, test_end) = (
+                log_time(field(&log, "BTEST", 3)),
+                log_time(field(&log, "BTEST", 10)),
+            );
+            assert_eq!(batch, start.naive_local());
+            assert!(batch <= test_start);
+            assert!(test_start < test_end, "{test_start} - {test_end}");
+        }
+    }
+
+    #[test]
+    fn unique_filenames() {
+        let generator = generator();
+        // all in the same second, like the boards of a multiboard
+        let now = time(2024, 2, 3, 10);
+        let names: std::collections::HashSet<String> = (0..1000)
+            .map(|i| generator.generate_filename(now, (i % 20 + 1) as u8))
+            .collect();
+        assert_eq!(names.len(), 1000);
+    }
+
+    #[test]
+    fn units() {
+        let mut generator = generator();
+        generator.board_types[0].tests = vec![
+            test("r1", TType::Resistor(95.0, 100.0, 105.0)),
+            test("c1", TType::Capacitor(9E-9, 1E-8, 1.1E-8)),
+        ];
+        let now = time(2024, 2, 3, 10);
+        generator.generate_multiboard(now);
+        let log = generator.preview().unwrap();
+
+        // {@A-RES|status|value|unit{@LIM3...
+        assert_eq!(field(&log, "A-RES", 3), "Ω{@LIM3");
+        assert_eq!(field(&log, "A-CAP", 3), "F{@LIM3");
+    }
+
+    #[test]
+    fn multiboard_braces() {
+        let mut generator = generator();
+        generator.board_types[0].panels = 4;
+        let now = time(2024, 2, 3, 10);
+        generator.generate_multiboard(now);
+
+        for log in generator.ict_logs() {
+            assert!(validate_braces(&log), "{log}");
+            // the board count ends @BATCH, which is closed after @BTEST
+            assert_eq!(field(&log, "BATCH", 15), "4");
+            assert!(log.ends_with("}\n}"), "{log}");
+        }
+    }
+
+    #[test]
+    fn braces_of_every_type() {
+        let mut generator = generator();
+        generator.board_types[0].panels = 1;
+        generator.pin_detail = true;
+        let now = time(2024, 2, 3, 10);
+
+        for name in TType::NAMES {
+            for format in LimitFormat::NAMES {
+                for fail_rate in [0, 100] {
+                    let mut test = test("t1", TType::from_name(name, 0.9, 1.0, 1.1).unwrap());
+                    test.fail_rate = Some(fail_rate);
+                    test.limit_format = LimitFormat::from_name(format).unwrap();
+                    generator.board_types[0].tests = vec![test];
+
+                    generator.generate_multiboard(now);
+                    let log = generator.preview().unwrap();
+                    assert!(validate_braces(&log), "{name}, {format}: {log}");
+                }
+            }
+        }
+
+        assert!(validate_braces("{@BATCH|{@BTEST|}}"));
+        assert!(!validate_braces("{@BATCH|{@BTEST|}"));
+        assert!(!validate_braces("{@BATCH|}}{"));
+    }
+
+    #[test]
+    fn station_id() {
+        let mut generator = generator();
+        generator.station_id = " 07|{B} ".to_string();
+        let now = time(2024, 2, 3, 10);
+        generator.generate_multiboard(now);
+
+        let log = generator.preview().unwrap();
+        assert_eq!(field(&log, "BATCH", 3), "07B");
+        assert_eq!(field(&log, "BATCH", 9), "i30704CE07BBZ01");
+        // {index}-{time}-{counter}I3070CE{station}BZ01
+        assert!(generator
+            .generate_filename(now, 1)
+            .ends_with("I3070CE07BBZ01"));
+    }
+
+    #[test]
+    fn last_id_rollover() {
+        let mut generator = generator();
+        let day = time(2024, 2, 3, 10);
+        generator.generate_multiboard(day);
+        assert_eq!(generator.last_id, 1);
+        generator.update_fields(day);
+        assert_eq!(generator.last_id, 21);
+
+        // kept over a restart on the same day
+        let mut restarted = Generator::from_config(generator.to_config());
+        assert_eq!(restarted.last_id, 21);
+        restarted.generate_multiboard(time(2024, 2, 3, 23));
+        assert_eq!(restarted.last_id, 21);
+
+        // and from 1 again the next day
+        restarted.generate_multiboard(time(2024, 2, 4, 0));
+        assert_eq!(restarted.last_id, 1);
+    }
+
+    #[test]
+    fn serial_wrap() {
+        assert_eq!(wrap_serial(0), 1);
+        assert_eq!(wrap_serial(99_999), 99_999);
+        assert_eq!(wrap_serial(100_000), 1);
+        assert_eq!(wrap_serial(100_019), 20);
+
+        let mut generator = generator();
+        let now = time(2024, 2, 3, 10);
+        generator.generate_multiboard(now);
+        generator.last_id = 99_990;
+        assert_eq!(&generator.generate_DMC(9, now)[8..13], "99999");
+        assert_eq!(&generator.generate_DMC(10, now)[8..13], "00001");
+
+        // stepped by the 20 boards
+        generator.update_fields(now);
+        assert_eq!(generator.last_id, 11);
+    }
+
+    #[test]
+    fn line_endings() {
+        let (mut generator, dir) = writing("line_endings");
+        generator.board_types[0].panels = 2;
+        generator.daily_file = true;
+        generator.line_ending = LineEnding::Crlf;
+        let ret = generator.generate_and_save();
+        generator.writer.flush();
+        let text = std::fs::read_to_string(dir.join("2024-02-03"));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(ret.is_ok());
+        let text = text.unwrap();
+        // an empty line after each log, and no bare LF
+        let records: Vec<&str> = text.split("\r\n\r\n").collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2], "");
+        for record in &records[..2] {
+            assert!(record.starts_with("{@BATCH|"));
+            assert!(validate_braces(record));
+        }
+        assert_eq!(text.matches('\n').count(), text.matches("\r\n").count());
+    }
+
+    #[test]
+    fn final_byte() {
+        for trailing_newline in [false, true] {
+            let (mut generator, dir) = writing(&format!("final_byte_{trailing_newline}"));
+            generator.board_types[0].panels = 2;
+            generator.line_ending = LineEnding::Lf;
+            generator.trailing_newline = trailing_newline;
+            let ret = generator.generate_and_save();
+            generator.writer.flush();
+            let files: Vec<Vec<u8>> = ret
+                .iter()
+                .flatten()
+                .map(|name| std::fs::read(dir.join(name)).unwrap_or_default())
+                .collect();
+            let _ = std::fs::remove_dir_all(&dir);
+
+            assert_eq!(files.len(), 2);
+            let last = if trailing_newline { b'\n' } else { b'}' };
+            for file in files {
+                assert_eq!(file.last(), Some(&last));
+                assert!(!file.ends_with(b"\n\n"));
+            }
+        }
+    }
+
+    #[test]
+    fn check_digits() {
+        assert_eq!(check_digit_mod43("CODE39"), 'W');
+        assert_eq!(check_digit_mod43("code39"), 'W');
+        assert_eq!(check_digit_mod10("629104150021"), '3');
+        assert_eq!(check_digit_mod10("0"), '0');
+
+        let mut generator = generator();
+        let now = time(2024, 2, 3, 10);
+        generator.generate_multiboard(now);
+        for (check_digit, calculate) in [
+            (CheckDigit::Mod43, check_digit_mod43 as fn(&str) -> char),
+            (CheckDigit::Mod10, check_digit_mod10),
+        ] {
+            generator.board_types[0].check_digit = check_digit;
+            let DMC = generator.generate_DMC(1, now);
+            let (payload, check) = DMC.split_at(DMC.len() - 1);
+            assert_eq!(payload, "L012403400002TB0001010111");
+            assert_eq!(check, calculate(payload).to_string());
+        }
+    }
+
+    #[test]
+    fn index_scheme() {
+        let mut generator = generator();
+        generator.board_types[0].panels = 2;
+        generator.board_types[0].tests = vec![
+            test("pins", TType::Pin),
+            test("r1", TType::Resistor(95.0, 100.0, 105.0)),
+        ];
+        let now = time(2024, 2, 3, 10);
+        generator.generate_multiboard(now);
+
+        // of the second board
+        for (scheme, position) in [
+            (IndexScheme::OneBased, "2"),
+            (IndexScheme::ZeroBased, "1"),
+            (IndexScheme::Letters, "B"),
+        ] {
+            generator.index_scheme = scheme;
+            let board = &generator.multiboard.boards[1];
+            let log = generator.generate_log(&generator.multiboard, board, now);
+            assert_eq!(field(&log, "PF", 1), format!("{position}%pins"));
+            assert_eq!(field(&log, "BLOCK", 1), format!("{position}%r1"));
+            // the board number of @BTEST stays 1-based
+            assert_eq!(field(&log, "BTEST", 12), "02");
+            assert!(generator
+                .generate_filename(now, board.index)
+                .starts_with(&format!("{position}-")));
+        }
+    }
+
+    #[test]
+    fn retest_start() {
+        let (mut generator, dir) = writing("retest_start");
+        generator.test_yield = 50.0;
+        generator.retest_rate = 100;
+        generator.retest_delay = 60.0;
+        generator.testing_time = 30.0;
+        generator.testing_jitter = 0.0;
+        for _ in 0..5 {
+            generator.save_retests(false);
+            assert!(generator.generate_and_save().is_ok());
+        }
+        generator.writer.flush();
+        let logs: Vec<String> = std::fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| std::fs::read_to_string(entry.path()).unwrap_or_default())
+            .collect();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // the first and the last @BTEST start of each DMC
+        let mut starts: std::collections::HashMap<String, Vec<NaiveDateTime>> =
+            std::collections::HashMap::new();
+        for log in &logs {
+            let DMC = field(log, "BTEST", 1).to_string();
+            starts
+                .entry(DMC)
+                .or_default()
+                .push(log_time(field(log, "BTEST", 3)));
+        }
+        let retested: Vec<&Vec<NaiveDateTime>> =
+            starts.values().filter(|times| times.len() > 1).collect();
+        assert!(!retested.is_empty());
+        for times in retested {
+            let (first, last) = (times.iter().min().unwrap(), times.iter().max().unwrap());
+            assert!(
+                *last - *first >= chrono::Duration::seconds(60),
+                "{first} - {last}"
+            );
+        }
+    }
+
+    #[test]
+    fn preview_keeps_type() {
+        let mut generator = generator();
+        let second = BoardType::from_config(BoardTypeConfig::default(), &mut generator.rng);
+        generator.board_types.push(second);
+        generator.type_order = TypeOrder::Cycle;
+        let now = time(2024, 2, 3, 10);
+
+        for _ in 0..3 {
+            generator.generate_preview(now);
+            assert_eq!(generator.multiboard.btype, 0);
+        }
+        generator.generate_multiboard(now);
+        assert_eq!(generator.multiboard.btype, 0);
+        generator.generate_preview(now);
+        assert_eq!(generator.multiboard.btype, 1);
+    }
+
+    #[test]
+    fn pair_fields() {
+        let mut generator = generator();
+        generator.set_sim_start(Some(time(2024, 2, 3, 10).naive_local()));
+        generator.testing_time = 30.0;
+        generator.testing_jitter = 0.0;
+        generator.roll_interval();
+        let now = generator.now();
+
+        assert!(generator.generate_pair().is_ok());
+        assert_eq!(generator.last_export, now);
+        assert_eq!(generator.last_cycle, 30.0);
+        assert_eq!(generator.exported, generator.multiboard.boards.len() as u32);
+    }
+}
@@ -0,0 +1,39 @@
+use serde::Serialize;
+
+// JSON output records. The field names are part of the output format, so keep them stable.
+
+#[derive(Serialize)]
+pub struct JsonBoard<'a> {
+    pub dmc: &'a str,
+    pub multiboard_dmc: &'a str,
+    pub index: u8,
+    pub result: &'a str, // "PASS" or "FAIL"
+    pub start: String,   // %y%m%d%H%M%S, same as in the ICT log, or ISO 8601
+    pub end: String,
+    pub tests: Vec<JsonTest<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct JsonTest<'a> {
+    pub name: &'a str,
+    #[serde(rename = "type")]
+    pub ttype: &'a str, // type name, as in the test definition files
+    pub result: &'a str, // "PASS" or "FAIL"
+    pub measured: Vec<f32>,
+    // these are null for pass/fail only tests
+    pub unit: Option<&'a str>,
+    pub min: Option<f32>,
+    pub nom: Option<f32>,
+    pub max: Option<f32>,
+    // limits of the extra measurements, at the end of measured; left out if there are none
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra: Vec<JsonLimits<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct JsonLimits<'a> {
+    pub unit: &'a str,
+    pub min: f32,
+    pub nom: f32,
+    pub max: f32,
+}
@@ -0,0 +1,20 @@
+#![allow(non_snake_case)]
+
+// The generation and the formatting of the logfiles, without the GUI, so other tools can use it:
+//
+//     let mut generator = Generator::from_config(Config::default());
+//     generator.generate_multiboard(generator.now());
+//     for log in generator.ict_logs() { ... }
+//
+// The ICT_log_maker binary is the GUI (and the headless mode) on top of this.
+
+pub mod config;
+pub mod generator;
+pub mod json;
+pub mod parser;
+pub mod server;
+pub mod stats;
+pub mod writer;
+
+pub use config::Config;
+pub use generator::{Board, Generator, MultiBoard, TType, Test};
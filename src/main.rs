@@ -1,332 +1,473 @@
 #![allow(non_snake_case)]
 
-use rand::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use eframe::egui;
 use egui::*;
 
-use chrono::{prelude::*, Duration};
+use ICT_log_maker::generator::validate_tests;
+use ICT_log_maker::{parser, Config, Generator};
+
+mod profiles;
+
+mod station;
+use station::Station;
 
 fn main() -> Result<(), eframe::Error> {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "--validate") {
+        if !validate_logs(&args[1..]) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.first().is_some_and(|arg| arg == "--roundtrip") {
+        if !roundtrip_logs(&args[1..]) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.iter().any(|arg| arg == "--headless") {
+        if let Err(e) = run_headless(&args) {
+            println!("ERR: {e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let options = eframe::NativeOptions {
         viewport: ViewportBuilder::default(),
         ..Default::default()
     };
 
     eframe::run_native(
-        "ICT Logfile Maker".as_str(),
+        "ICT Logfile Maker",
         options,
-        Box::new(|_cc| Box::<MyApp>::default()),
+        Box::new(|_cc| Box::new(MyApp::from_config(Config::load()))),
     )
 }
 
-// Test type + limits (min, nom, max)
-enum TType {
-    Pin,
-    Capacitor(f32, f32, f32),
-    Resistor(f32, f32, f32),
-}
-
-struct Test {
-    name: String,
-    ttype: TType,
-}
-
-impl Test {
-    // generates a random measurement between a the limits if is_ok, or one between 0 and min if not.
-    fn get_measurement(&self, is_ok: bool) -> f32 {
-        match self.ttype {
-            TType::Pin => 0.0,
-            TType::Capacitor(min, _, max) | TType::Resistor(min, _, max) => {
-                if is_ok {
-                    rand::thread_rng().gen_range(min..max)
-                } else {
-                    // ToDo: could add a failure mode, when meas > max
-                    rand::thread_rng().gen_range(0.0..min)
-                }
+// Generates N multiboards on every station of the saved (or the given) config, then exits.
+// Usage: ICT_log_maker --headless [--count N] [--config path]
+// The log format is checked first, see parser::self_test.
+fn run_headless(args: &[String]) -> Result<(), String> {
+    let mut count: u32 = 1;
+    let mut config: Option<Config> = None;
+    let mut config_path: Option<PathBuf> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" => {}
+            "--count" => {
+                count = args
+                    .next()
+                    .and_then(|c| c.parse().ok())
+                    .ok_or("--count needs a number")?;
             }
+            "--config" => {
+                let path = args.next().ok_or("--config needs a path")?;
+                config = Some(
+                    Config::load_from(Path::new(path))
+                        .map_err(|e| format!("Loading config {path} failed: {e}"))?,
+                );
+                config_path = Some(PathBuf::from(path));
+            }
+            _ => return Err(format!("Unknown argument: {arg}")),
         }
     }
-}
-
-// Create dummy tests.
-// 1x pin test
-// 10x capacitor test (limits are +- 10-30%)
-// 10x resistor test (limits are +- 1-5%)
-fn populate_tests() -> Vec<Test> {
-    use TType::*;
-    let mut ret: Vec<Test> = vec![Test {
-        name: "pins".to_string(),
-        ttype: Pin,
-    }];
-
-    let mut rng = rand::thread_rng();
-    for i in 1..=10 {
-        let nominal: f32 = rng.gen_range(1E-12..1E-6);
-        let min = nominal * rng.gen_range(0.7..0.9);
-        let max = nominal * rng.gen_range(1.1..1.3);
-        ret.push(Test {
-            name: format!("c{i:02.0}"),
-            ttype: Capacitor(min, nominal, max),
-        })
-    }
 
-    let mut rng = rand::thread_rng();
-    for i in 1..=10 {
-        let nominal: f32 = rng.gen_range(1E0..1E6);
-        let min = nominal * rng.gen_range(0.95..0.99);
-        let max = nominal * rng.gen_range(1.01..1.05);
-        ret.push(Test {
-            name: format!("r{i:02.0}"),
-            ttype: Resistor(min, nominal, max),
-        })
+    // Every station of the config generates N multiboards
+    let mut config = config.unwrap_or_else(Config::load);
+    let dark_mode = config.dark_mode;
+    let stations = std::mem::take(&mut config.stations);
+    let mut generators: Vec<Generator> = std::iter::once(config)
+        .chain(stations)
+        .map(Generator::from_config)
+        .collect();
+
+    // before anything is written, so a broken format doesn't waste a long run
+    for generator in &generators {
+        let problems = parser::self_test(generator.to_config());
+        for problem in &problems {
+            println!("ERR: Self-test: {problem}");
+        }
+        if !problems.is_empty() {
+            return Err("Self-test failed, nothing was generated".to_string());
+        }
     }
 
-    ret
-}
-
-struct TResult {
-    ok: bool,
-    measured: f32,
-}
-
-// Logfiles use 00 or 0 to sign OK tests, and 01 or 1 for NOK
-impl TResult {
-    fn to_short(&self) -> &str {
-        if self.ok {
-            return "0";
+    let mut configs: Vec<Config> = Vec::new();
+    for generator in &mut generators {
+        for board_type in &generator.board_types {
+            if let Err(e) = validate_tests(&board_type.tests) {
+                println!("WARN: {}:\n{e}", board_type.name);
+            }
+        }
+        for _ in 0..count {
+            generator
+                .generate_and_save()
+                .map_err(|e| format!("Saving results failed: {e}"))?;
+            for line in generator.writer.take_dry_run() {
+                println!("{line}");
+            }
         }
 
-        "1"
+        // No waiting in headless mode, the retests are saved right away
+        generator.save_retests(true);
+        generator.writer.flush();
+        generator
+            .writer
+            .take_error()
+            .map_err(|e| format!("Saving results failed: {e}"))?;
+        configs.push(generator.to_config());
     }
 
-    fn to_str(&self) -> &str {
-        if self.ok {
-            return "00";
-        }
-
-        "01"
+    // Saved back like the GUI does on exit, so the next run continues the serials
+    let mut configs = configs.into_iter();
+    let first = configs.next().unwrap_or_default();
+    let config = Config {
+        dark_mode,
+        stations: configs.collect(),
+        ..first
+    };
+    match config_path {
+        Some(path) => config.save_to(&path),
+        None => config.save(),
     }
+    .map_err(|e| format!("Saving the config failed: {e}"))
 }
 
-struct Board {
-    DMC: String,
-    index: u8,
-    results: Vec<TResult>,
+// Parses the given logfiles and prints a short report of each.
+// Usage: ICT_log_maker --validate file...
+// Returns false if any of them could not be parsed.
+fn validate_logs(paths: &[String]) -> bool {
+    let mut ret = true;
+
+    for path in paths {
+        let result = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parser::parse_log(&text).map_err(|e| e.to_string()));
+
+        match result {
+            Ok(board) => println!(
+                "{path}: OK, DMC: {}, index: {}, tests: {}, failed: {}",
+                board.DMC,
+                board.index,
+                board.results.len(),
+                board.results.iter().filter(|r| !r.ok).count()
+            ),
+            Err(e) => {
+                println!("{path}: ERR: {e}");
+                ret = false;
+            }
+        }
+    }
+
+    ret
 }
 
-// Used only in @BTEST header
-impl Board {
-    fn get_result(&self) -> &str {
-        for res in &self.results {
-            if !res.ok {
-                return "01";
+// Regenerates each captured log from its own tests and results, and lists where the two differ.
+// Returns false if any of them differs, or can't be read.
+fn roundtrip_logs(paths: &[String]) -> bool {
+    let mut ret = true;
+    // the settings of the saved config, but nothing is written
+    let mut generator = Generator::from_config(Config {
+        dry_run: true,
+        ..Config::load()
+    });
+
+    for path in paths {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                println!("{path}: ERR: {e}");
+                ret = false;
+                continue;
+            }
+        };
+
+        match parser::roundtrip(&mut generator, &text) {
+            Ok(diffs) if diffs.is_empty() => println!("{path}: OK"),
+            Ok(diffs) => {
+                println!("{path}: {} mismatches", diffs.len());
+                for diff in diffs {
+                    println!("  {diff}");
+                }
+                ret = false;
+            }
+            Err(e) => {
+                println!("{path}: ERR: {e}");
+                ret = false;
             }
         }
-
-        "00"
     }
-}
 
-#[derive(Default)]
-struct MultiBoard {
-    DMC: String,
-    boards: Vec<Board>,
+    ret
 }
 
 struct MyApp {
-    output_dir: PathBuf, // ToDo: add directory sellection window
+    stations: Vec<Station>,
+    current: usize, // station shown
 
-    enabled: bool,
+    profile_name: String,
+    profiles: Vec<String>, // saved ones, refreshed on save and delete
 
-    test_yield: u8, //0-100%
-    panels: u8, // how many pcbs are on a multiboard
-    testing_time: i64, // in seconds
-
-    start_time: String,
-    last_export: DateTime<Local>,
-
-    last_id: u16,
-    tests: Vec<Test>,
-    multiboard: MultiBoard,
+    dark_mode: bool,
+    show_files: bool, // side panel of the written files
+    closing: bool,    // the window is kept open until the queued files are written
 }
 
 impl MyApp {
-    fn its_time(&self) -> bool {
-        self.enabled && (Local::now() - self.last_export > Duration::seconds(self.testing_time))
-    }
+    // The top level of the config is the first station, the others are in config.stations
+    fn from_config(mut config: Config) -> Self {
+        let dark_mode = config.dark_mode;
+        let others = std::mem::take(&mut config.stations);
 
-    fn should_pass(&self) -> bool {
-        rand::thread_rng().gen_range(0..100) < self.test_yield
+        Self {
+            stations: std::iter::once(config)
+                .chain(others)
+                .map(Station::from_config)
+                .collect(),
+            current: 0,
+            profile_name: String::new(),
+            profiles: profiles::list(),
+            dark_mode,
+            show_files: false,
+            closing: false,
+        }
     }
 
-    fn generate_results(&self) -> Vec<TResult> {
-        let mut ret: Vec<TResult> = Vec::new();
-
-        // ToDo: only produce analog tests if pins test is OK.
-        for test in &self.tests {
-            let is_ok = self.should_pass();
-            ret.push(TResult {
-                ok: is_ok,
-                measured: test.get_measurement(is_ok),
-            })
+    fn to_config(&self) -> Config {
+        let mut configs = self.stations.iter().map(Station::to_config);
+        let first = configs.next().unwrap_or_default();
+        Config {
+            dark_mode: self.dark_mode,
+            stations: configs.collect(),
+            ..first
         }
-
-        ret
     }
+}
 
-    fn generate_DMC(&self, index: u8) -> String {
-        let date: NaiveDate = Local::now().date_naive();
-        let YY = date.year(); // will return 2024, but we only need the second half? Can use the first half as line ID.
-        let DoY = date.ordinal();
+impl MyApp {
+    // A copy of the shown station, writing to its own folder with the next station ID
+    fn add_station(&mut self) {
+        let n = self.stations.len() + 1;
+        let mut config = self.stations[self.current].to_config();
+
+        let mut dir = config.output_dir.into_os_string();
+        dir.push(format!("_{n}"));
+        config.output_dir = PathBuf::from(dir);
+
+        let width = config.station_id.len();
+        if let Some(max) = self
+            .stations
+            .iter()
+            .filter_map(|station| station.generator.station_id.parse::<u32>().ok())
+            .max()
+        {
+            config.station_id = format!("{:0width$}", max + 1);
+        }
 
-        format!(
-            "L{YY:04.0}{DoY:03.0}{:05.0}TB0001010111",
-            self.last_id + index as u16
-        )
+        self.stations.push(Station::from_config(config));
+        self.current = self.stations.len() - 1;
     }
 
-    fn generate_multiboard(&mut self) {
-        self.multiboard.boards.clear();
+    // Tabs of the stations
+    fn stations_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            for (i, station) in self.stations.iter().enumerate() {
+                let mut label = format!("Station {}", station.generator.station_id);
+                if station.enabled {
+                    label += " ●";
+                }
+                ui.selectable_value(&mut self.current, i, label);
+            }
 
-        self.multiboard.DMC = self.generate_DMC(0);
-        for i in 0..self.panels {
-            self.multiboard.boards.push(Board {
-                DMC: self.generate_DMC(i),
-                index: i + 1,
-                results: self.generate_results(),
-            })
-        }
+            ui.separator();
+            if ui.button("Add").clicked() {
+                self.add_station();
+            }
+            if ui
+                .add_enabled(self.stations.len() > 1, egui::Button::new("Remove"))
+                .clicked()
+            {
+                // its queued files are still written, the writer finishes them on drop
+                self.stations.remove(self.current);
+                self.current = self.current.min(self.stations.len() - 1);
+            }
+        });
     }
 
-    fn update_fields(&mut self) {
-        self.last_export = Local::now();
-        self.last_id += self.panels as u16;
-    }
+    // Profiles are saved from and loaded into the shown station
+    fn profiles_ui(&mut self, ui: &mut Ui) {
+        let station = &mut self.stations[self.current];
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("profiles")
+                .selected_text(self.profile_name.clone())
+                .show_ui(ui, |ui| {
+                    for name in &self.profiles {
+                        ui.selectable_value(&mut self.profile_name, name.clone(), name);
+                    }
+                });
+            ui.text_edit_singleline(&mut self.profile_name);
+        });
 
-    fn generate_filename(&self, time_now: DateTime<Local>, index: u8) -> String {
-        format!("{index}-{}I3070CE0101BZ01", time_now.format("%y%m%d%H%M%S"))
-    }
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                station.last_error = profiles::save(&self.profile_name, &station.generator)
+                    .err()
+                    .map(|e| format!("ERR: Saving the profile failed: {e}"));
+                self.profiles = profiles::list();
+            }
 
-    fn generate_log(&self, board: &Board, start: &String) -> String {
-        let mut lines: Vec<String> = Vec::new();
-
-        lines.push(format!(
-            "{{@BATCH|DUMMY||0101|1||btest|{}||i30704CE0101BZ01|DUMMY|RevA|DUMMY||D",
-            self.start_time
-        ));
-        lines.push(format!(
-            "{{@BTEST|{}|{}|{}|000000|0|all||n|n|{}||{:02.0}|{}",
-            board.DMC,
-            board.get_result(),
-            start,
-            Local::now().format("%y%m%d%H%M%S"),
-            board.index,
-            self.multiboard.DMC
-        ));
-
-        for (test, result) in self.tests.iter().zip(board.results.iter()) {
-            match test.ttype {
-                TType::Pin => {
-                    lines.push(format!(
-                        "{{@PF|{}%pins|{}|0",
-                        board.index,
-                        result.to_short()
-                    ));
-                    lines.push("}".to_string());
-                }
-                TType::Capacitor(min, nom, max) => {
-                    lines.push(format!(
-                        "{{@BLOCK|{}%{}|{}",
-                        board.index,
-                        test.name,
-                        result.to_str()
-                    ));
-                    lines.push(format!(
-                        "{{@A-CAP|{}|{:+E}{{@LIM3|{:+E}|{:+E}|{:+E}}}}}",
-                        result.to_short(),
-                        result.measured,
-                        nom,
-                        max,
-                        min
-                    ));
-                    lines.push("}".to_string());
-                }
-                TType::Resistor(min, nom, max) => {
-                    lines.push(format!(
-                        "{{@BLOCK|{}%{}|{}",
-                        board.index,
-                        test.name,
-                        result.to_str()
-                    ));
-                    lines.push(format!(
-                        "{{@A-RES|{}|{:+E}{{@LIM3|{:+E}|{:+E}|{:+E}}}}}",
-                        result.to_short(),
-                        result.measured,
-                        nom,
-                        max,
-                        min
-                    ));
-                    lines.push("}".to_string());
+            let exists = self.profiles.contains(&self.profile_name);
+            if ui.add_enabled(exists, egui::Button::new("Load")).clicked() {
+                match profiles::load(&self.profile_name) {
+                    // Everything restarts with the new settings, like on startup
+                    // the serials continue, a profile must not reissue the ones of the day
+                    Ok(mut config) => {
+                        let current = station.to_config();
+                        config.last_id = current.last_id;
+                        config.last_id_date = current.last_id_date;
+                        *station = Station::from_config(config);
+                    }
+                    Err(e) => {
+                        station.last_error = Some(format!("ERR: Loading the profile failed: {e}"))
+                    }
                 }
             }
-        }
 
-        lines.push("}}".to_string());
-        lines.join("\n")
+            if ui
+                .add_enabled(exists, egui::Button::new("Delete"))
+                .clicked()
+            {
+                station.last_error = profiles::delete(&self.profile_name)
+                    .err()
+                    .map(|e| format!("ERR: Deleting the profile failed: {e}"));
+                self.profiles = profiles::list();
+            }
+        });
     }
+}
 
-    fn save_results(&self) -> std::io::Result<()> {
-        let now = Local::now();
-        let start_t = format!("{}", self.last_export.format("%y%m%d%H%M%S"));
+impl Default for MyApp {
+    fn default() -> Self {
+        Self::from_config(Config::default())
+    }
+}
 
-        for board in &self.multiboard.boards {
-            let path = self
-                .output_dir
-                .join(self.generate_filename(now, board.index));
-            println!("New path: {:?}", path);
-            std::fs::write(path, self.generate_log(board, &start_t))?;
+impl MyApp {
+    // On close the generation stops, the pending retests are saved, and the window stays open
+    // with a "Saving..." message until every queued file is written.
+    fn shutdown_ui(&mut self, ctx: &egui::Context) {
+        for station in &mut self.stations {
+            if station.enabled {
+                station.set_enabled(false);
+            }
+            station.generator.stop_batch();
+            station.generator.save_retests(true);
+        }
+
+        let pending: usize = self
+            .stations
+            .iter()
+            .map(|station| station.generator.writer.pending())
+            .sum();
+        if pending == 0 {
+            ctx.send_viewport_cmd(ViewportCommand::Close);
+            return;
         }
 
-        Ok(())
+        ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.centered_and_justified(|ui| {
+                ui.heading(format!("Saving... {pending} files left"));
+            });
+        });
+        ctx.request_repaint_after(std::time::Duration::from_millis(50));
     }
 }
 
-impl Default for MyApp {
-    fn default() -> Self {
-        Self {
-            output_dir: PathBuf::from("D:\\Rust\\_Logs\\Dummy"),
-            enabled: false,
-            test_yield: 99,
-            panels: 20,
-            testing_time: 30,
-            last_export: Local::now(),
-            start_time: format!("{}", Local::now().format("%y%m%d%H%M%S")),
-            last_id: 1,
-            tests: populate_tests(),
-            multiboard: MultiBoard::default(),
+impl eframe::App for MyApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // normally done by shutdown_ui already
+        for station in &mut self.stations {
+            station.generator.save_retests(true);
+            station.generator.writer.flush();
+        }
+
+        if let Err(e) = self.to_config().save() {
+            log::error!("saving the config failed: {e}");
         }
     }
-}
 
-impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        if ctx.style().visuals.dark_mode != self.dark_mode {
+            ctx.set_visuals(if self.dark_mode {
+                Visuals::dark()
+            } else {
+                Visuals::light()
+            });
+        }
+
+        if ctx.input(|i| i.viewport().close_requested()) {
+            self.closing = true;
+        }
+        if self.closing {
+            self.shutdown_ui(ctx);
+            return;
+        }
+
+        for station in &mut self.stations {
+            station.update(ctx);
+        }
+
+        // Shortcuts for the shown station, only while no widget has the focus,
+        // so typing into a field (or pressing space on a button) doesn't trigger them
+        if ctx.memory(|memory| memory.focus().is_none()) {
+            let station = &mut self.stations[self.current];
+            if ctx.input(|i| i.key_pressed(Key::Space)) {
+                station.set_enabled(!station.enabled);
+            }
+            if ctx.input(|i| i.key_pressed(Key::F5)) {
+                station.generate();
+            }
+            if ctx.input(|i| i.key_pressed(Key::F6)) {
+                station.preview();
+            }
+        }
 
-        if self.its_time() {
-            self.generate_multiboard();
-            self.save_results().expect("ERR: Saving reults failed!");
-            self.update_fields()
+        if self.show_files {
+            egui::SidePanel::right("files_panel")
+                .resizable(true)
+                .default_width(350.0)
+                .show(ctx, |ui| {
+                    self.stations[self.current].files_ui(ui);
+                });
         }
 
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.checkbox(&mut self.enabled, "Enable");
-            ui.monospace(format!("Last ID:{}", self.last_id));
-            ui.add(egui::Slider::new(&mut self.panels, 1..=20).text("Panels on MB"));
-            ui.add(egui::Slider::new(&mut self.testing_time, 5..=60).text("Test time"));
+            self.stations_ui(ui);
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.dark_mode, "Dark mode");
+                ui.checkbox(&mut self.show_files, "Written files");
+                ui.small("Keys: Space enable/disable, F5 generate one, F6 preview");
+            });
+            ui.collapsing("Profiles", |ui| {
+                self.profiles_ui(ui);
+            });
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                self.stations[self.current].ui(ui);
+            });
         });
     }
 }
@@ -0,0 +1,639 @@
+use std::fmt;
+
+use chrono::NaiveDateTime;
+
+use crate::config::{BoardTypeConfig, Config, RailedValue};
+use crate::generator::{
+    format_value, Board, Generator, LimitFormat, Measurement, MultiBoard, TResult, TType, Test,
+    OVERLOAD,
+};
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// One {@TAG|field|field...{child}{child}} block of the logfile
+pub struct Node {
+    pub tag: String,
+    pub fields: Vec<String>,
+    pub children: Vec<Node>,
+    pub line: usize,
+}
+
+struct Scanner<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            chars: text.chars().peekable(),
+            line: 1,
+        }
+    }
+
+    fn error(&self, msg: &str) -> ParseError {
+        ParseError {
+            line: self.line,
+            msg: msg.to_string(),
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.next();
+        }
+    }
+
+    // Reads until the next '|', '{' or '}', or the end of the line
+    fn read_field(&mut self) -> String {
+        let mut ret = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if matches!(c, '|' | '{' | '}' | '\n' | '\r') {
+                break;
+            }
+            ret.push(c);
+            self.next();
+        }
+
+        ret
+    }
+
+    // Parses a block, starting at its opening brace
+    fn parse_node(&mut self) -> Result<Node, ParseError> {
+        self.skip_whitespace();
+        let line = self.line;
+        if self.next() != Some('{') {
+            return Err(self.error("expected '{'"));
+        }
+
+        let tag = self.read_field();
+        if !tag.starts_with('@') {
+            return Err(self.error(&format!("expected a tag, found \"{tag}\"")));
+        }
+
+        let mut fields: Vec<String> = Vec::new();
+        while self.chars.peek() == Some(&'|') {
+            self.next();
+            fields.push(self.read_field());
+        }
+
+        let mut children: Vec<Node> = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek().copied() {
+                Some('{') => children.push(self.parse_node()?),
+                Some('}') => {
+                    self.next();
+                    break;
+                }
+                Some(c) => return Err(self.error(&format!("unexpected '{c}' in {tag}"))),
+                None => return Err(self.error(&format!("unclosed {tag} block"))),
+            }
+        }
+
+        Ok(Node {
+            tag: tag[1..].to_string(),
+            fields,
+            children,
+            line,
+        })
+    }
+}
+
+// Parses the whole logfile into its top level blocks
+pub fn parse_nodes(text: &str) -> Result<Vec<Node>, ParseError> {
+    let mut scanner = Scanner::new(text);
+    let mut ret: Vec<Node> = Vec::new();
+
+    loop {
+        scanner.skip_whitespace();
+        if scanner.chars.peek().is_none() {
+            break;
+        }
+        ret.push(scanner.parse_node()?);
+    }
+
+    Ok(ret)
+}
+
+fn find_node<'a>(nodes: &'a [Node], tag: &str) -> Option<&'a Node> {
+    for node in nodes {
+        if node.tag == tag {
+            return Some(node);
+        }
+        if let Some(found) = find_node(&node.children, tag) {
+            return Some(found);
+        }
+    }
+
+    None
+}
+
+fn parse_value(node: &Node, field: Option<&String>) -> Result<f32, ParseError> {
+    let text = field.map(|f| f.as_str()).unwrap_or_default();
+    text.parse().map_err(|_| ParseError {
+        line: node.line,
+        msg: format!("invalid number \"{text}\" in {}", node.tag),
+    })
+}
+
+// Analog measurement line: {@A-XXX|status|value|unit{@LIM3|nom|max|min}}
+// Pass/fail only lines (jumpers) have no value. A railed value (OVLD) is infinite.
+fn parse_measurement(node: &Node) -> Result<Option<f32>, ParseError> {
+    for limit in &node.children {
+        if !limit.tag.starts_with("LIM") {
+            return Err(ParseError {
+                line: limit.line,
+                msg: format!("unexpected {} in {}", limit.tag, node.tag),
+            });
+        }
+        for field in &limit.fields {
+            parse_value(limit, Some(field))?;
+        }
+    }
+
+    match node.fields.get(1) {
+        Some(value) if value == OVERLOAD => Ok(Some(f32::INFINITY)),
+        Some(value) => Ok(Some(parse_value(node, Some(value))?)),
+        None => Ok(None),
+    }
+}
+
+// Inverse of Generator::generate_log.
+// Tests left out of the log (after a failed pins test) are not in the results,
+// and the results are in the order of the log.
+pub fn parse_log(text: &str) -> Result<Board, ParseError> {
+    let nodes = parse_nodes(text)?;
+    let btest = find_node(&nodes, "BTEST").ok_or(ParseError {
+        line: 1,
+        msg: "no @BTEST block".to_string(),
+    })?;
+
+    let DMC = btest.fields.first().cloned().unwrap_or_default();
+    let index = btest
+        .fields
+        .get(11)
+        .and_then(|i| i.parse().ok())
+        .ok_or(ParseError {
+            line: btest.line,
+            msg: "missing board index in @BTEST".to_string(),
+        })?;
+
+    let mut results: Vec<TResult> = Vec::new();
+    for node in &btest.children {
+        match node.tag.as_str() {
+            // {@PF|index%pins|status|open count, with a {@PIN|node} per open pin
+            "PF" => results.push(TResult {
+                ok: node.fields.get(1).is_some_and(|s| s == "0"),
+                tested: true,
+                measured: Vec::new(),
+                open_nodes: node
+                    .children
+                    .iter()
+                    .filter(|pin| pin.tag == "PIN")
+                    .map(|pin| pin.fields.first().cloned().unwrap_or_default())
+                    .collect(),
+                railed: Vec::new(),
+            }),
+            // {@BLOCK|index%name|status, with the measurement lines as children
+            "BLOCK" => {
+                let mut measured: Vec<f32> = Vec::new();
+                for child in &node.children {
+                    if let Some(value) = parse_measurement(child)? {
+                        measured.push(value);
+                    }
+                }
+
+                let railed = if measured.iter().any(|value| value.is_infinite()) {
+                    measured.iter().map(|value| value.is_infinite()).collect()
+                } else {
+                    Vec::new()
+                };
+                results.push(TResult {
+                    ok: node.fields.get(1).is_some_and(|s| s == "00"),
+                    tested: true,
+                    measured,
+                    open_nodes: Vec::new(),
+                    railed,
+                });
+            }
+            _ => {
+                return Err(ParseError {
+                    line: node.line,
+                    msg: format!("unexpected {} in @BTEST", node.tag),
+                })
+            }
+        }
+    }
+
+    // from the start and end timestamps, 0 if they can't be read
+    let time = |i: usize| {
+        let text = btest.fields.get(i)?;
+        NaiveDateTime::parse_from_str(text, "%y%m%d%H%M%S").ok()
+    };
+    let duration = match (time(2), time(9)) {
+        (Some(start), Some(end)) => (end - start).num_seconds() as f64,
+        _ => 0.0,
+    };
+
+    Ok(Board {
+        DMC,
+        index,
+        order: (0..results.len()).collect(),
+        results,
+        duration,
+        truncated: None,
+    })
+}
+
+// Unit and limits of a measurement line, with the format of its limit block.
+// @LIM2 has no nominal, the middle of the limits is used. Without a limit block
+// the limits are all the measured value, or 0 for the pass/fail only lines.
+fn parse_spec(node: &Node) -> Result<(Measurement, LimitFormat), ParseError> {
+    let unit = node.fields.get(2).cloned().unwrap_or_default();
+    let spec = |min, nom, max| Measurement {
+        unit: unit.clone(),
+        min,
+        nom,
+        max,
+    };
+
+    Ok(match node.children.first() {
+        // nom|max|min
+        Some(limit) if limit.tag == "LIM3" => (
+            spec(
+                parse_value(limit, limit.fields.get(2))?,
+                parse_value(limit, limit.fields.first())?,
+                parse_value(limit, limit.fields.get(1))?,
+            ),
+            LimitFormat::Lim3,
+        ),
+        // max|min
+        Some(limit) if limit.tag == "LIM2" => {
+            let min = parse_value(limit, limit.fields.get(1))?;
+            let max = parse_value(limit, limit.fields.first())?;
+            (spec(min, (min + max) / 2.0, max), LimitFormat::Lim2)
+        }
+        Some(limit) => {
+            return Err(ParseError {
+                line: limit.line,
+                msg: format!("unknown limits @{}", limit.tag),
+            })
+        }
+        None => {
+            let value = parse_measurement(node)?.unwrap_or_default();
+            (spec(value, value, value), LimitFormat::None)
+        }
+    })
+}
+
+// Test definition of a {@PF} or {@BLOCK} block. The type comes from the tag of the first
+// measurement line, and the limits and their format from its limit block. Transistors are
+// @A-DIO blocks with two lines of the same limits, any other line is an extra measurement.
+fn parse_test(node: &Node) -> Result<Test, ParseError> {
+    let error = |msg: String| ParseError {
+        line: node.line,
+        msg,
+    };
+
+    let mut extra: Vec<Measurement> = Vec::new();
+    let mut limit_format = LimitFormat::Lim3;
+    let ttype = if node.tag == "PF" {
+        TType::Pin
+    } else {
+        let first = node
+            .children
+            .first()
+            .ok_or_else(|| error(format!("empty {} block", node.tag)))?;
+        let (own, format) = parse_spec(first)?;
+        limit_format = format;
+
+        let mut specs: Vec<Measurement> = Vec::new();
+        for line in node.children.iter().skip(1) {
+            specs.push(parse_spec(line)?.0);
+        }
+        let transistor = first.tag == "A-DIO"
+            && node.children.get(1).is_some_and(|line| line.tag == "A-DIO")
+            && (format == LimitFormat::None || specs.first() == Some(&own));
+        extra = specs.split_off(transistor as usize);
+
+        let name = match first.tag.as_str() {
+            "A-DIO" if transistor => "transistor",
+            "A-JUM" => "jumper",
+            "A-CAP" => "capacitor",
+            "A-RES" => "resistor",
+            "A-DIO" => "diode",
+            "A-ZEN" => "zener",
+            "A-LED" => "led",
+            "A-IND" => "inductor",
+            tag => return Err(error(format!("unknown measurement {tag}"))),
+        };
+        if name == "jumper" {
+            limit_format = LimitFormat::Lim3; // not logged anyway
+        }
+
+        TType::from_name(name, own.min, own.nom, own.max).unwrap_or(TType::Jumper)
+    };
+
+    // index%name
+    let name = node
+        .fields
+        .first()
+        .and_then(|field| field.split_once('%'))
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_default();
+
+    Ok(Test {
+        name,
+        ttype,
+        fail_rate: None,
+        drift: None,
+        tempco: None,
+        extra,
+        limit_format,
+    })
+}
+
+impl Board {
+    // Loads a captured logfile as a template: its board, and the tests it was logged with,
+    // in the same order. With the two, Generator::generate_log rebuilds the same log,
+    // see compare_logs.
+    pub fn from_template(text: &str) -> Result<(Board, Vec<Test>), ParseError> {
+        let board = parse_log(text)?;
+        let nodes = parse_nodes(text)?;
+        let mut tests: Vec<Test> = Vec::new();
+        if let Some(btest) = find_node(&nodes, "BTEST") {
+            for node in &btest.children {
+                tests.push(parse_test(node)?);
+            }
+        }
+
+        Ok((board, tests))
+    }
+}
+
+// Numbers are compared by value, so "1E0" matches "+1.000E0"
+fn same_field(a: &str, b: &str) -> bool {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => (a - b).abs() <= a.abs().max(b.abs()) * 1e-5,
+        _ => a == b,
+    }
+}
+
+fn compare_nodes(expected: &Node, actual: &Node, path: &str, ret: &mut Vec<String>) {
+    let path = format!("{path}/@{}", expected.tag);
+    if expected.tag != actual.tag {
+        ret.push(format!(
+            "line {}: {path}: found @{} instead (line {})",
+            expected.line, actual.tag, actual.line
+        ));
+        return;
+    }
+
+    // the unit of a measurement is compared only if the template has one, not every
+    // dialect logs it
+    let measurement = expected.tag.starts_with("A-");
+    let unitless = measurement && expected.fields.len() == 2 && actual.fields.len() == 3;
+    if expected.fields.len() != actual.fields.len() && !unitless {
+        ret.push(format!(
+            "line {}: {path}: {} fields instead of {} (line {})",
+            expected.line,
+            actual.fields.len(),
+            expected.fields.len(),
+            actual.line
+        ));
+    }
+
+    // timestamps, serials and station names: only the result and the board index are kept
+    let ignored = |i: usize| match expected.tag.as_str() {
+        "BATCH" => true,
+        "BTEST" => i != 1 && i != 11,
+        _ => false,
+    };
+    for (i, (a, b)) in expected.fields.iter().zip(&actual.fields).enumerate() {
+        let no_unit = measurement && i == 2 && a.is_empty();
+        if !ignored(i) && !no_unit && !same_field(a, b) {
+            ret.push(format!(
+                "line {}: {path}: field {} is \"{b}\" instead of \"{a}\" (line {})",
+                expected.line,
+                i + 1,
+                actual.line
+            ));
+        }
+    }
+
+    for (a, b) in expected.children.iter().zip(&actual.children) {
+        compare_nodes(a, b, &path, ret);
+    }
+    for missing in expected.children.iter().skip(actual.children.len()) {
+        ret.push(format!(
+            "line {}: {path}: missing @{}",
+            missing.line, missing.tag
+        ));
+    }
+    for extra in actual.children.iter().skip(expected.children.len()) {
+        ret.push(format!("line {}: {path}: extra @{}", extra.line, extra.tag));
+    }
+}
+
+// Structural differences between a captured log and a generated one, from the @BATCH block.
+// The metadata lines before it, the timestamps and the serials are ignored.
+pub fn compare_logs(expected: &str, actual: &str) -> Result<Vec<String>, ParseError> {
+    let expected = parse_nodes(expected)?;
+    let actual = parse_nodes(actual)?;
+    let mut ret: Vec<String> = Vec::new();
+
+    match (find_node(&expected, "BATCH"), find_node(&actual, "BATCH")) {
+        (Some(a), Some(b)) => compare_nodes(a, b, "", &mut ret),
+        (None, _) => ret.push("no @BATCH block in the template".to_string()),
+        (_, None) => ret.push("no @BATCH block in the generated log".to_string()),
+    }
+
+    Ok(ret)
+}
+
+// Regenerates a log from its own tests and results (with the settings of the generator, on its
+// first board type), and compares the two, see compare_logs
+pub fn roundtrip(generator: &mut Generator, text: &str) -> Result<Vec<String>, ParseError> {
+    let (board, tests) = Board::from_template(text)?;
+    generator.board_types[0].tests = tests;
+    let multiboard = MultiBoard {
+        DMC: board.DMC.clone(),
+        boards: vec![board],
+        ..Default::default()
+    };
+    let board = &multiboard.boards[0];
+    let now = generator.now();
+    let log = generator.generate_log(&multiboard, board, now);
+    compare_logs(text, &log)
+}
+
+// Built-in check of the log format, with the settings of config: a log of each test type in
+// each limit format, passing and failing, is parsed back and compared to what was generated,
+// then round tripped. Returns the problems, empty if none.
+pub fn self_test(config: Config) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::new();
+    let mut generator = Generator::from_config(Config {
+        board_types: vec![BoardTypeConfig {
+            panels: 1,
+            ..Default::default()
+        }],
+        stations: Vec::new(),
+        ..config
+    });
+
+    for name in TType::NAMES {
+        for format in LimitFormat::NAMES {
+            for fail_rate in [0, 100] {
+                let Some(ttype) = TType::from_name(name, 0.9, 1.0, 1.1) else {
+                    continue;
+                };
+                generator.board_types[0].tests = vec![Test {
+                    name: "t1".to_string(),
+                    ttype,
+                    fail_rate: Some(fail_rate),
+                    drift: None,
+                    tempco: None,
+                    extra: Vec::new(),
+                    limit_format: LimitFormat::from_name(format).unwrap_or_default(),
+                }];
+
+                let outcome = if fail_rate == 0 { "passing" } else { "failing" };
+                if let Err(e) = self_test_log(&mut generator) {
+                    ret.push(format!("{name}, {format}, {outcome}: {e}"));
+                }
+            }
+        }
+    }
+
+    ret
+}
+
+fn self_test_log(generator: &mut Generator) -> Result<(), String> {
+    let now = generator.now();
+    generator.generate_multiboard(now);
+    let multiboard = &generator.multiboard;
+    let board = &multiboard.boards[0];
+    let log = generator.generate_log(multiboard, board, now);
+
+    let parsed = parse_log(&log).map_err(|e| e.to_string())?;
+    if parsed.DMC != board.DMC {
+        return Err(format!("DMC {} instead of {}", parsed.DMC, board.DMC));
+    }
+    let tested: Vec<&TResult> = board
+        .order
+        .iter()
+        .map(|&i| &board.results[i])
+        .filter(|result| result.tested)
+        .collect();
+    if parsed.results.len() != tested.len() {
+        return Err(format!(
+            "{} results instead of {}",
+            parsed.results.len(),
+            tested.len()
+        ));
+    }
+
+    // the values as they were logged
+    let logged = |result: &TResult, i: usize, value: f32| {
+        if result.is_railed(i) && generator.railed_value == RailedValue::Ovld {
+            f32::INFINITY
+        } else {
+            format_value(value, generator.precision)
+                .parse()
+                .unwrap_or(f32::NAN)
+        }
+    };
+    let analog = generator.board_types[0].tests[0]
+        .ttype
+        .get_limits()
+        .is_some();
+    for (parsed, expected) in parsed.results.iter().zip(tested) {
+        if parsed.ok != expected.ok {
+            return Err(format!("parsed as {}", parsed.to_str()));
+        }
+        let values: Vec<f32> = expected
+            .measured
+            .iter()
+            .enumerate()
+            .map(|(i, value)| logged(expected, i, *value))
+            .collect();
+        if analog && parsed.measured != values {
+            return Err(format!(
+                "measured {:?} instead of {values:?}",
+                parsed.measured
+            ));
+        }
+    }
+
+    let diffs = roundtrip(generator, &log).map_err(|e| e.to_string())?;
+    if !diffs.is_empty() {
+        return Err(format!("round trip: {}", diffs.join(", ")));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    // Seeded, and writes nothing. The low yield fails some boards.
+    fn generator() -> Generator {
+        Generator::from_config(Config {
+            seed: Some(1),
+            dry_run: true,
+            test_yield: 90.0,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn parse_log_round_trip() {
+        let mut generator = generator();
+        let now = Local.with_ymd_and_hms(2024, 2, 3, 10, 0, 0).unwrap();
+        generator.generate_multiboard(now);
+
+        let boards = &generator.multiboard.boards;
+        assert!(boards.iter().any(|board| board.get_result() != "00"));
+        for board in boards {
+            let log = generator.generate_log(&generator.multiboard, board, now);
+            let parsed = parse_log(&log).unwrap();
+            assert_eq!(parsed.DMC, board.DMC);
+            assert_eq!(parsed.index, board.index);
+            assert_eq!(parsed.get_result(), board.get_result());
+
+            // in the order of the log, without the ones left out
+            let expected: Vec<bool> = board
+                .order
+                .iter()
+                .map(|&i| &board.results[i])
+                .filter(|result| result.tested)
+                .map(|result| result.ok)
+                .collect();
+            let actual: Vec<bool> = parsed.results.iter().map(|result| result.ok).collect();
+            assert_eq!(actual, expected, "{}", board.DMC);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::io;
+use std::path::PathBuf;
+
+use ICT_log_maker::generator::{save_tests, Generator};
+use ICT_log_maker::Config;
+
+// Named snapshots of the settings and the tests, in a profiles folder next to the executable.
+// Each profile is a folder with the config, and the tests of every board type as CSV:
+// profiles/<name>/config.json
+// profiles/<name>/tests_<board type index>.csv
+
+fn dir() -> io::Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    Ok(exe.with_file_name("profiles"))
+}
+
+// The name becomes a folder name, so it can't be a path
+fn profile_dir(name: &str) -> io::Result<PathBuf> {
+    let name = name.trim();
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', ':']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid profile name \"{name}\""),
+        ));
+    }
+
+    Ok(dir()?.join(name))
+}
+
+// Names of the saved profiles, sorted
+pub fn list() -> Vec<String> {
+    let mut ret: Vec<String> = dir()
+        .and_then(std::fs::read_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().join("config.json").is_file())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    ret.sort();
+    ret
+}
+
+// Saves the current tests too, even the ones edited in the GUI,
+// so the profile doesn't depend on the original test files.
+pub fn save(name: &str, generator: &Generator) -> io::Result<()> {
+    let dir = profile_dir(name)?;
+    std::fs::create_dir_all(&dir)?;
+
+    let mut config = generator.to_config();
+    for (i, (board_type, type_config)) in generator
+        .board_types
+        .iter()
+        .zip(config.board_types.iter_mut())
+        .enumerate()
+    {
+        let path = dir.join(format!("tests_{i}.csv"));
+        save_tests(&path, &board_type.tests)?;
+        type_config.tests_file = Some(path);
+    }
+
+    config.save_to(&dir.join("config.json"))
+}
+
+pub fn load(name: &str) -> io::Result<Config> {
+    Config::load_from(&profile_dir(name)?.join("config.json"))
+}
+
+pub fn delete(name: &str) -> io::Result<()> {
+    std::fs::remove_dir_all(profile_dir(name)?)
+}
@@ -0,0 +1,163 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// Most boards of one request, generated as a batch of its station
+pub const MAX_COUNT: u32 = 10_000;
+
+// How long a request waits for the generation
+const REPLY_TIMEOUT: Duration = Duration::from_secs(300);
+
+// A "generate count boards" request, answered with the written files or an error
+pub struct Request {
+    pub count: u32,
+    reply: Sender<Result<Vec<String>, String>>,
+}
+
+impl Request {
+    pub fn respond(self, result: Result<Vec<String>, String>) {
+        // the client could be gone already
+        let _ = self.reply.send(result);
+    }
+}
+
+// Minimal HTTP server for remote control, on localhost only:
+// POST /generate?count=N generates (at least) N boards, and returns the files as JSON,
+// {"files": [...]} or {"error": "..."}.
+// The connections are served one at a time on a background thread, while the requests are
+// done by the owner of the generator, see poll. wake is called for each request, so the owner
+// can pick it up right away.
+pub struct Server {
+    pub port: u16,
+    requests: Receiver<Request>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    pub fn start(port: u16, wake: impl Fn() + Send + 'static) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        // so the thread can check the stop flag between the connections
+        listener.set_nonblocking(true)?;
+
+        let (requests_tx, requests) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = std::thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if let Err(e) = handle(stream, &requests_tx, &wake) {
+                            log::error!("remote control: {e}");
+                        }
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(Duration::from_millis(50));
+                    }
+                    Err(e) => {
+                        log::error!("remote control: {e}");
+                        std::thread::sleep(Duration::from_millis(50));
+                    }
+                }
+            }
+        });
+
+        log::info!("remote control on port {port}");
+        Ok(Self {
+            port,
+            requests,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    // The next waiting request, if any
+    pub fn poll(&self) -> Option<Request> {
+        self.requests.try_recv().ok()
+    }
+}
+
+impl Drop for Server {
+    // The waiting requests are dropped, so their clients are answered right away
+    // instead of the thread waiting for the replies until REPLY_TIMEOUT
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            while !thread.is_finished() {
+                while self.requests.try_recv().is_ok() {}
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            let _ = thread.join();
+        }
+    }
+}
+
+fn respond(mut stream: TcpStream, status: &str, body: serde_json::Value) -> io::Result<()> {
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+fn handle(stream: TcpStream, requests: &Sender<Request>, wake: &impl Fn()) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    // "POST /generate?count=N HTTP/1.1", then the headers until an empty line.
+    // The body, if any, is not used.
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let error = |msg: &str| serde_json::json!({ "error": msg });
+
+    if path != "/generate" {
+        return respond(stream, "404 Not Found", error("unknown path"));
+    }
+    if method != "POST" {
+        return respond(stream, "405 Method Not Allowed", error("use POST"));
+    }
+
+    let count = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("count="))
+        .map_or(Some(1), |count| count.parse().ok())
+        .filter(|count| (1..=MAX_COUNT).contains(count));
+    let Some(count) = count else {
+        let msg = format!("count must be 1-{MAX_COUNT}");
+        return respond(stream, "400 Bad Request", error(&msg));
+    };
+
+    let (reply, replies) = mpsc::channel();
+    if requests.send(Request { count, reply }).is_err() {
+        return respond(stream, "503 Service Unavailable", error("not running"));
+    }
+    wake();
+
+    match replies.recv_timeout(REPLY_TIMEOUT) {
+        Ok(Ok(files)) => respond(stream, "200 OK", serde_json::json!({ "files": files })),
+        Ok(Err(e)) => respond(stream, "500 Internal Server Error", error(&e)),
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            respond(stream, "503 Service Unavailable", error("timed out"))
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            respond(stream, "503 Service Unavailable", error("stopped"))
+        }
+    }
+}
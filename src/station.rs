@@ -0,0 +1,1603 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use eframe::egui;
+use egui::*;
+use egui_plot::{Bar, BarChart, Plot, VLine};
+
+use ICT_log_maker::config::{
+    BoardTypeConfig, CheckDigit, Config, ForcedFailure, FullScale, IndexScheme, LineEnding,
+    OutputFormat, RailedValue, Resolution, Subfolders, TestOrder, TypeOrder,
+};
+use ICT_log_maker::generator::{
+    parse_sim_time, populate_tests, save_tests, validate_tests, BoardType, Generator, LimitFormat,
+    TType, Test, MAX_PANELS, SIM_TIME_FORMAT,
+};
+use ICT_log_maker::parser;
+use ICT_log_maker::server::{Request, Server};
+use ICT_log_maker::stats::Stats;
+
+const MAX_UNDO: usize = 50; // steps
+
+// The tests of a board type before an edit
+struct UndoStep {
+    btype: usize,
+    tests: Vec<Test>,
+    tests_file: Option<PathBuf>,
+    tests_source: String,
+}
+
+impl UndoStep {
+    fn of(btype: usize, board_type: &BoardType) -> Self {
+        Self {
+            btype,
+            tests: board_type.tests.clone(),
+            tests_file: board_type.tests_file.clone(),
+            tests_source: board_type.tests_source.clone(),
+        }
+    }
+}
+
+// Undo stack of the test lists, for Ctrl+Z. The oldest steps are dropped after MAX_UNDO.
+#[derive(Default)]
+struct Undo {
+    steps: Vec<UndoStep>,
+    start: Option<UndoStep>, // the tests when the current edit started
+    focus: Option<egui::Id>, // of the last frame, an edit starts when it moves
+}
+
+impl Undo {
+    // Pushes the tests from the start of the edit. Dragging a value or typing a name
+    // is one edit, so only its first change is pushed.
+    fn edited(&mut self) {
+        if let Some(step) = self.start.take() {
+            self.push(step);
+        }
+    }
+
+    fn push(&mut self, step: UndoStep) {
+        self.start = None;
+        if self.steps.len() >= MAX_UNDO {
+            self.steps.remove(0);
+        }
+        self.steps.push(step);
+    }
+
+    fn pop(&mut self) -> Option<UndoStep> {
+        self.start = None;
+        self.steps.pop()
+    }
+
+    // The steps refer to the types by index, like the retests in Generator::remove_board_type
+    fn remove_type(&mut self, btype: usize) {
+        self.start = None;
+        self.steps.retain(|step| step.btype != btype);
+        for step in &mut self.steps {
+            if step.btype > btype {
+                step.btype -= 1;
+            }
+        }
+    }
+}
+
+// One simulated tester: its own generator with its own output, tests and timer,
+// and the GUI state that goes with it. The app shows each one in a tab.
+pub struct Station {
+    pub generator: Generator,
+    output_dir_edit: String, // text field buffer for output_dir
+    sim_start_edit: String,  // text field buffer for generator.sim_start
+    test_order_edit: String, // names of TestOrder::Custom, comma separated
+
+    pub enabled: bool,
+    pub last_error: Option<String>,
+
+    // Stop after this many boards. Since whole multiboards are exported,
+    // the last one can overshoot it.
+    target_count: Option<u32>,
+    target_start: u32, // value of generator.exported when the target was (re)started
+
+    batch_count: u32, // boards for "Generate now"
+
+    rate_start: Option<(Instant, u32)>, // when it was enabled, and generator.exported then
+
+    preview: String,
+
+    edited_type: usize, // board type shown in the editor
+    undo: Undo,
+
+    stats: Stats,
+    plot_test: Option<String>, // test shown on the histogram
+
+    opened_file: Option<(PathBuf, String)>, // shown in the files panel, with its contents
+    self_test: Option<Vec<String>>,         // problems found by the last one
+
+    server: Option<Server>,   // remote control, see generator.remote_port
+    server_port: Option<u16>, // the port it was started on, even if that failed
+    remote: Option<(Request, Vec<String>)>, // request of the running batch, and its files so far
+}
+
+impl Station {
+    pub fn from_config(config: Config) -> Self {
+        let generator = Generator::from_config(config);
+
+        // a week back is a sensible default for backfilling
+        let sim_start = generator
+            .sim_start
+            .unwrap_or_else(|| (chrono::Local::now() - chrono::Duration::days(7)).naive_local());
+
+        let test_order_edit = match &generator.test_order {
+            TestOrder::Custom(names) => names.join(", "),
+            _ => String::new(),
+        };
+
+        Self {
+            output_dir_edit: generator.output_dir.display().to_string(),
+            sim_start_edit: sim_start.format(SIM_TIME_FORMAT).to_string(),
+            test_order_edit,
+            generator,
+            enabled: false,
+            last_error: None,
+            target_count: None,
+            target_start: 0,
+            batch_count: 1000,
+            rate_start: None,
+            preview: String::new(),
+            edited_type: 0,
+            undo: Undo::default(),
+            stats: Stats::default(),
+            plot_test: None,
+            opened_file: None,
+            self_test: None,
+            server: None,
+            server_port: None,
+            remote: None,
+        }
+    }
+
+    pub fn to_config(&self) -> Config {
+        self.generator.to_config()
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.generator.set_paused(!enabled);
+        self.rate_start = enabled.then(|| (Instant::now(), self.generator.exported));
+    }
+
+    // Exported boards per minute since it was enabled, in real time.
+    // None until the first multiboard is out.
+    fn rate(&self) -> Option<f64> {
+        let (start, exported) = self.rate_start?;
+        let boards = self.generator.exported - exported;
+        let minutes = start.elapsed().as_secs_f64() / 60.0;
+        (boards > 0).then(|| boards as f64 / minutes)
+    }
+}
+
+// Limits can span many decades, so they are shown in scientific notation
+fn limit_edit(value: &mut f32) -> egui::DragValue<'_> {
+    let speed = value.abs() as f64 * 0.001;
+    egui::DragValue::new(value)
+        .speed(speed)
+        .custom_formatter(|v, _| format!("{v:E}"))
+        .custom_parser(|text| text.trim().parse().ok())
+}
+
+impl Station {
+    // Editable list of the tests. Tests without an own fail rate use the global yield.
+    fn tests_table(&mut self, ui: &mut Ui) {
+        let mut changed = false;
+        let mut remove: Option<usize> = None;
+
+        let test_yield = self.generator.test_yield;
+        let board_type = &mut self.generator.board_types[self.edited_type];
+        // the tests are copied only when an edit can start: on a click, or a focus change
+        let focus = ui.memory(|memory| memory.focus());
+        if ui.input(|input| input.pointer.any_pressed()) || focus != self.undo.focus {
+            self.undo.start = Some(UndoStep::of(self.edited_type, board_type));
+        }
+        self.undo.focus = focus;
+
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                egui::Grid::new("tests_grid").striped(true).show(ui, |ui| {
+                    ui.label("Name");
+                    ui.label("Type");
+                    ui.label("Min");
+                    ui.label("Nom");
+                    ui.label("Max");
+                    ui.label("Fail rate");
+                    ui.label("Drift/h");
+                    ui.label("Tempco ppm/°C");
+                    ui.label("Limits");
+                    ui.label("Extra");
+                    ui.end_row();
+
+                    for (i, test) in board_type.tests.iter_mut().enumerate() {
+                        changed |= ui
+                            .add(egui::TextEdit::singleline(&mut test.name).desired_width(60.0))
+                            .changed();
+
+                        egui::ComboBox::from_id_source(("test_type", i))
+                            .selected_text(test.ttype.get_name().to_string())
+                            .show_ui(ui, |ui| {
+                                for name in TType::NAMES {
+                                    if ui
+                                        .selectable_label(test.ttype.get_name() == name, name)
+                                        .clicked()
+                                    {
+                                        // keep the limits when switching between analog types
+                                        let (min, nom, max) =
+                                            test.ttype.get_limits().unwrap_or((0.9, 1.0, 1.1));
+                                        if let Some(ttype) = TType::from_name(name, min, nom, max) {
+                                            test.ttype = ttype;
+                                            changed = true;
+                                        }
+                                    }
+                                }
+                            });
+
+                        if let Some((min, nom, max)) = test.ttype.get_limits_mut() {
+                            changed |= ui.add(limit_edit(min)).changed();
+                            changed |= ui.add(limit_edit(nom)).changed();
+                            changed |= ui.add(limit_edit(max)).changed();
+                        } else {
+                            ui.label("-");
+                            ui.label("-");
+                            ui.label("-");
+                        }
+
+                        ui.horizontal(|ui| {
+                            let mut own = test.fail_rate.is_some();
+                            let mut rate =
+                                test.fail_rate.unwrap_or((100.0 - test_yield).round() as u8);
+                            changed |= ui.checkbox(&mut own, "").changed();
+                            if own {
+                                changed |= ui
+                                    .add(
+                                        egui::DragValue::new(&mut rate)
+                                            .clamp_range(0..=100)
+                                            .suffix("%"),
+                                    )
+                                    .changed();
+                            }
+                            test.fail_rate = own.then_some(rate);
+                        });
+
+                        if let Some((_, nom, _)) = test.ttype.get_limits() {
+                            ui.horizontal(|ui| {
+                                let mut drifting = test.drift.is_some();
+                                let mut drift = test.drift.unwrap_or(nom * 0.01);
+                                changed |= ui.checkbox(&mut drifting, "").changed();
+                                if drifting {
+                                    changed |= ui.add(limit_edit(&mut drift)).changed();
+                                }
+                                test.drift = drifting.then_some(drift);
+                            });
+
+                            // unchecked uses the default of the type
+                            ui.horizontal(|ui| {
+                                let mut own = test.tempco.is_some();
+                                let mut tempco = test.get_tempco();
+                                changed |= ui.checkbox(&mut own, "").changed();
+                                if own {
+                                    changed |= ui.add(egui::DragValue::new(&mut tempco)).changed();
+                                } else {
+                                    ui.label(format!("{tempco}"));
+                                }
+                                test.tempco = own.then_some(tempco);
+                            });
+                        } else {
+                            ui.label("-");
+                            ui.label("-");
+                        }
+
+                        if test.ttype.get_limits().is_some() {
+                            egui::ComboBox::from_id_source(("limit_format", i))
+                                .selected_text(test.limit_format.get_name().to_string())
+                                .show_ui(ui, |ui| {
+                                    for name in LimitFormat::NAMES {
+                                        if let Some(format) = LimitFormat::from_name(name) {
+                                            changed |= ui
+                                                .selectable_value(
+                                                    &mut test.limit_format,
+                                                    format,
+                                                    name,
+                                                )
+                                                .changed();
+                                        }
+                                    }
+                                });
+                        } else {
+                            ui.label("-");
+                        }
+
+                        // set in the test file, see load_tests
+                        if test.extra.is_empty() {
+                            ui.label("-");
+                        } else {
+                            let units: Vec<&str> =
+                                test.extra.iter().map(|m| m.unit.as_str()).collect();
+                            ui.label(units.join(", ")).on_hover_text(
+                                test.extra
+                                    .iter()
+                                    .map(|m| {
+                                        format!("{}: {:E} {:E} {:E}", m.unit, m.min, m.nom, m.max)
+                                    })
+                                    .collect::<Vec<_>>()
+                                    .join("\n"),
+                            );
+                        }
+
+                        if ui.button("Remove").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+
+        let mut added = false;
+        let mut undo = false;
+        ui.horizontal(|ui| {
+            if ui.button("Add test").clicked() {
+                let name = format!("t{:02.0}", board_type.tests.len() + 1);
+                board_type.tests.push(Test {
+                    name,
+                    ttype: TType::Resistor(95.0, 100.0, 105.0),
+                    fail_rate: None,
+                    drift: None,
+                    tempco: None,
+                    extra: Vec::new(),
+                    limit_format: LimitFormat::Lim3,
+                });
+                added = true;
+            }
+            let undoable = !self.undo.steps.is_empty();
+            undo = ui
+                .add_enabled(undoable, egui::Button::new("Undo"))
+                .on_hover_text("Ctrl+Z")
+                .clicked();
+        });
+
+        if let Some(i) = remove {
+            board_type.tests.remove(i);
+        }
+
+        if changed || added || remove.is_some() {
+            self.undo.edited();
+            if !board_type.tests_source.ends_with("(edited)") {
+                board_type.tests_source += " (edited)";
+            }
+            if added || remove.is_some() {
+                self.tests_replaced(self.edited_type);
+            } else {
+                self.update_preview();
+            }
+        }
+
+        if undo {
+            self.undo_tests();
+        }
+    }
+
+    // Back to the tests before the last edit
+    fn undo_tests(&mut self) {
+        if let Some(step) = self.undo.pop() {
+            if let Some(board_type) = self.generator.board_types.get_mut(step.btype) {
+                board_type.tests = step.tests;
+                board_type.tests_file = step.tests_file;
+                board_type.tests_source = step.tests_source;
+                self.edited_type = step.btype;
+                self.tests_replaced(step.btype);
+            }
+        }
+    }
+
+    // Selecting, adding and removing board types, and the settings of the selected one
+    fn board_types_ui(&mut self, ui: &mut Ui) {
+        let types = &mut self.generator.board_types;
+        let mut remove = false;
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("board_type")
+                .selected_text(types[self.edited_type].name.clone())
+                .show_ui(ui, |ui| {
+                    for (i, board_type) in types.iter().enumerate() {
+                        ui.selectable_value(&mut self.edited_type, i, &board_type.name);
+                    }
+                });
+
+            if ui.button("Add").clicked() {
+                let mut board_type =
+                    BoardType::from_config(BoardTypeConfig::default(), &mut self.generator.rng);
+                board_type.name = format!("Board {}", types.len() + 1);
+                types.push(board_type);
+                self.edited_type = types.len() - 1;
+            }
+
+            if ui
+                .add_enabled(types.len() > 1, egui::Button::new("Remove"))
+                .clicked()
+            {
+                remove = true;
+            }
+
+            ui.separator();
+            ui.radio_value(&mut self.generator.type_order, TypeOrder::Cycle, "Cycle");
+            ui.radio_value(&mut self.generator.type_order, TypeOrder::Random, "Random");
+        });
+
+        if remove {
+            self.generator.remove_board_type(self.edited_type);
+            self.undo.remove_type(self.edited_type);
+            self.edited_type = self.edited_type.saturating_sub(1);
+            self.preview.clear();
+        }
+
+        let mut loaded = false; // new tests, the preview is updated
+        let board_type = &mut self.generator.board_types[self.edited_type];
+        egui::Grid::new("board_type_grid").show(ui, |ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut board_type.name);
+            ui.end_row();
+            ui.label("DMC:");
+            ui.text_edit_singleline(&mut board_type.dmc_template);
+            ui.end_row();
+            ui.label("Check digit:");
+            ui.horizontal(|ui| {
+                let check_digit = &mut board_type.check_digit;
+                ui.radio_value(check_digit, CheckDigit::None, "None");
+                ui.radio_value(check_digit, CheckDigit::Mod43, "Mod 43");
+                ui.radio_value(check_digit, CheckDigit::Mod10, "Mod 10");
+            });
+            ui.end_row();
+        });
+        ui.add(egui::Slider::new(&mut board_type.panels, 1..=MAX_PANELS).text("Panels on MB"));
+        ui.checkbox(
+            &mut board_type.shared_dmc,
+            "Shared panel DMC (boards differ only in index)",
+        );
+
+        ui.horizontal(|ui| {
+            if ui.button("Load tests").clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("CSV", &["csv"])
+                    .add_filter("JSON", &["json"])
+                    .pick_file()
+                {
+                    self.undo.push(UndoStep::of(self.edited_type, board_type));
+                    board_type.load_tests_from(&path);
+                    loaded = true;
+                }
+            }
+            // the current tests, the random ones too, so they can be loaded back later
+            if ui.button("Export tests").clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("CSV", &["csv"])
+                    .add_filter("JSON", &["json"])
+                    .set_file_name("tests.csv")
+                    .save_file()
+                {
+                    self.last_error = save_tests(&path, &board_type.tests)
+                        .err()
+                        .map(|e| format!("ERR: Exporting the tests failed: {e}"));
+                }
+            }
+            ui.label(format!("Tests: {}", board_type.tests_source));
+        });
+        if let Err(e) = validate_tests(&board_type.tests) {
+            ui.colored_label(Color32::RED, e);
+        }
+
+        ui.collapsing("Random tests", |ui| {
+            let counts = &mut board_type.random_tests;
+            egui::Grid::new("random_tests_grid").show(ui, |ui| {
+                for (label, count) in [
+                    ("Jumpers:", &mut counts.jumpers),
+                    ("Capacitors:", &mut counts.capacitors),
+                    ("Resistors:", &mut counts.resistors),
+                    ("Inductors:", &mut counts.inductors),
+                    ("Diodes:", &mut counts.diodes),
+                    ("Zeners:", &mut counts.zeners),
+                    ("LEDs:", &mut counts.leds),
+                    ("Transistors:", &mut counts.transistors),
+                ] {
+                    ui.label(label);
+                    ui.add(egui::DragValue::new(count).clamp_range(0..=1000));
+                    ui.end_row();
+                }
+            });
+
+            egui::Grid::new("nominal_ranges_grid").show(ui, |ui| {
+                let ranges = &mut board_type.nominal_ranges;
+                for (label, (from, to), unit) in [
+                    ("Capacitor nominals:", &mut ranges.capacitors, "F"),
+                    ("Resistor nominals:", &mut ranges.resistors, "Ω"),
+                ] {
+                    ui.label(label);
+                    ui.horizontal(|ui| {
+                        ui.add(limit_edit(from));
+                        ui.label("to");
+                        ui.add(limit_edit(to));
+                        ui.label(unit);
+                    });
+                    ui.end_row();
+                }
+            });
+
+            // replaces the loaded tests too
+            if ui.button("Generate").clicked() {
+                self.undo.push(UndoStep::of(self.edited_type, board_type));
+                board_type.tests = populate_tests(
+                    &mut self.generator.rng,
+                    &board_type.random_tests,
+                    &board_type.nominal_ranges,
+                );
+                board_type.tests_file = None;
+                board_type.tests_source = "random".to_string();
+                loaded = true;
+            }
+        });
+
+        if loaded {
+            self.tests_replaced(self.edited_type);
+        }
+
+        ui.collapsing("Tests", |ui| {
+            self.tests_table(ui);
+        });
+    }
+
+    // Working hours of the virtual clock
+    fn schedule_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let mut scheduled = self.generator.schedule.is_some();
+            let mut schedule = self.generator.schedule.unwrap_or_default();
+            ui.checkbox(&mut scheduled, "Shifts only, from");
+            if scheduled {
+                ui.add(egui::DragValue::new(&mut schedule.start_hour).clamp_range(0..=23));
+                ui.label("to");
+                ui.add(
+                    egui::DragValue::new(&mut schedule.end_hour)
+                        .clamp_range(schedule.start_hour + 1..=24),
+                );
+                ui.label("h on");
+                for (day, active) in ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"]
+                    .iter()
+                    .zip(schedule.weekdays.iter_mut())
+                {
+                    ui.checkbox(active, *day);
+                }
+            }
+            self.generator.schedule = scheduled.then_some(schedule);
+        });
+    }
+
+    // Tests which always fail, on every board or on the one with the given DMC or index
+    fn forced_failures_ui(&mut self, ui: &mut Ui) {
+        let mut remove: Option<usize> = None;
+        let forced_failures = &mut self.generator.forced_failures;
+
+        egui::Grid::new("forced_grid").striped(true).show(ui, |ui| {
+            ui.label("Test");
+            ui.label("Board (DMC or index, empty for all)");
+            ui.end_row();
+
+            for (i, forced) in forced_failures.iter_mut().enumerate() {
+                ui.add(egui::TextEdit::singleline(&mut forced.test).desired_width(80.0));
+                ui.text_edit_singleline(&mut forced.board);
+                if ui.button("Remove").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+
+        if let Some(i) = remove {
+            forced_failures.remove(i);
+        }
+        if ui.button("Add").clicked() {
+            forced_failures.push(ForcedFailure::default());
+        }
+    }
+
+    // Range of the tester per unit, for railed measurements
+    fn full_scale_ui(&mut self, ui: &mut Ui) {
+        let mut remove: Option<usize> = None;
+        let full_scale = &mut self.generator.full_scale;
+
+        egui::Grid::new("full_scale_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Unit");
+                ui.label("Full scale");
+                ui.end_row();
+
+                for (i, scale) in full_scale.iter_mut().enumerate() {
+                    ui.add(egui::TextEdit::singleline(&mut scale.unit).desired_width(40.0));
+                    ui.add(egui::DragValue::new(&mut scale.value).speed(0.1));
+                    if ui.button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some(i) = remove {
+            full_scale.remove(i);
+        }
+        if ui.button("Add").clicked() {
+            full_scale.push(FullScale::default());
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Railed measurements are logged as");
+            let railed = &mut self.generator.railed_value;
+            let ovld = ui.radio_value(railed, RailedValue::Ovld, "OVLD");
+            let full_scale = ui.radio_value(railed, RailedValue::FullScale, "the full scale");
+            if ovld.changed() || full_scale.changed() {
+                self.update_preview();
+            }
+        });
+    }
+}
+
+impl Station {
+    // The files written so far, the newest on top. Clicking one shows it, as it is on the disk.
+    pub fn files_ui(&mut self, ui: &mut Ui) {
+        let written = self.generator.writer.written();
+        ui.heading(format!("Written files ({})", written.len()));
+
+        let mut open: Option<PathBuf> = None; // clicked, or reloaded
+
+        egui::ScrollArea::vertical()
+            .id_source("files_scroll")
+            .max_height(ui.available_height() / 2.0)
+            .show(ui, |ui| {
+                for path in written.iter().rev() {
+                    // relative to output_dir, which is shown above anyway
+                    let name = path
+                        .strip_prefix(&self.generator.output_dir)
+                        .unwrap_or(path)
+                        .display()
+                        .to_string();
+                    let selected = self.opened_file.as_ref().is_some_and(|(p, _)| p == path);
+                    if ui.selectable_label(selected, name).clicked() {
+                        open = Some(path.clone());
+                    }
+                }
+            });
+
+        if let Some((path, _)) = &self.opened_file {
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(path.display().to_string());
+                if ui.button("Reload").clicked() {
+                    open = Some(path.clone());
+                }
+            });
+        }
+        if let Some(path) = open {
+            let text = std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| format!("ERR: reading failed: {e}"));
+            self.opened_file = Some((path, text));
+        }
+
+        if let Some((_, text)) = &self.opened_file {
+            egui::ScrollArea::both()
+                .id_source("file_scroll")
+                .show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut text.as_str())
+                            .code_editor()
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+        }
+    }
+
+    fn stats_ui(&mut self, ui: &mut Ui) {
+        let percent = |y: Option<f32>| y.map(|y| format!("{y:.1}%")).unwrap_or("-".to_string());
+
+        egui::Grid::new("stats_grid").show(ui, |ui| {
+            ui.label("Boards:");
+            ui.monospace(format!("{}", self.stats.boards));
+            ui.end_row();
+            ui.label("Passed:");
+            ui.monospace(format!("{}", self.stats.passed));
+            ui.end_row();
+            ui.label("Failed:");
+            ui.monospace(format!("{}", self.stats.failed));
+            ui.end_row();
+            ui.label("Yield:");
+            ui.monospace(percent(self.stats.total_yield()));
+            ui.end_row();
+            ui.label("Rolling yield:");
+            ui.monospace(percent(self.stats.rolling_yield()));
+            ui.end_row();
+        });
+
+        ui.collapsing("Fails per test", |ui| {
+            egui::ScrollArea::vertical()
+                .id_source("fails_scroll")
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("fails_grid").striped(true).show(ui, |ui| {
+                        for (name, count) in self.stats.top_fails() {
+                            ui.label(name);
+                            ui.monospace(format!("{count}"));
+                            ui.end_row();
+                        }
+                    });
+                });
+        });
+
+        ui.collapsing("Histogram", |ui| {
+            self.histogram_ui(ui);
+        });
+
+        ui.collapsing("Cycle times", |ui| {
+            self.cycle_times_ui(ui);
+        });
+
+        if ui.button("Reset").clicked() {
+            self.stats = Stats::default();
+        }
+    }
+
+    // Distribution of the recent measurements of the selected test, with its limits
+    fn histogram_ui(&mut self, ui: &mut Ui) {
+        const BINS: usize = 30;
+
+        egui::ComboBox::from_id_source("plot_test")
+            .selected_text(self.plot_test.clone().unwrap_or("-".to_string()))
+            .show_ui(ui, |ui| {
+                for name in self.stats.measured_tests() {
+                    ui.selectable_value(&mut self.plot_test, Some(name.clone()), name);
+                }
+            });
+
+        let Some(m) = self
+            .plot_test
+            .as_ref()
+            .and_then(|name| self.stats.measurements.get(name))
+        else {
+            return;
+        };
+
+        // the range includes the limits, so they are always visible
+        let (low, high) = m.limits.map(|(min, _, max)| (min, max)).unwrap_or_default();
+        let values: Vec<f64> = m.values.iter().map(|value| *value as f64).collect();
+        let (bars, width) = histogram(&values, low as f64, high as f64, BINS);
+
+        Plot::new("histogram")
+            .height(200.0)
+            .allow_scroll(false)
+            .show(ui, |plot_ui| {
+                plot_ui.bar_chart(BarChart::new(bars).width(width));
+                if let Some((min, nom, max)) = m.limits {
+                    plot_ui.vline(VLine::new(min).color(Color32::RED).name("min"));
+                    plot_ui.vline(VLine::new(nom).color(Color32::GREEN).name("nom"));
+                    plot_ui.vline(VLine::new(max).color(Color32::RED).name("max"));
+                }
+            });
+    }
+
+    // Intervals between the recent exports, against the set testing time and its jitter
+    fn cycle_times_ui(&mut self, ui: &mut Ui) {
+        const BINS: usize = 20;
+
+        let Some((min, avg, max)) = self.stats.cycle_summary() else {
+            ui.label("No cycles yet");
+            return;
+        };
+
+        egui::Grid::new("cycle_grid").show(ui, |ui| {
+            ui.label("Min:");
+            ui.monospace(format!("{min:.2} s"));
+            ui.end_row();
+            ui.label("Avg:");
+            ui.monospace(format!("{avg:.2} s"));
+            ui.end_row();
+            ui.label("Max:");
+            ui.monospace(format!("{max:.2} s"));
+            ui.end_row();
+        });
+
+        let nominal = self.generator.testing_time;
+        let jitter = nominal * self.generator.testing_jitter.clamp(0.0, 100.0) / 100.0;
+        let values: Vec<f64> = self.stats.cycle_times.iter().copied().collect();
+        let (bars, width) = histogram(&values, nominal - jitter, nominal + jitter, BINS);
+
+        Plot::new("cycle_histogram")
+            .height(150.0)
+            .allow_scroll(false)
+            .show(ui, |plot_ui| {
+                plot_ui.bar_chart(BarChart::new(bars).width(width));
+                plot_ui.vline(
+                    VLine::new(nominal)
+                        .color(Color32::GREEN)
+                        .name("testing time"),
+                );
+                if jitter > 0.0 {
+                    for time in [nominal - jitter, nominal + jitter] {
+                        plot_ui.vline(VLine::new(time).color(Color32::RED).name("jitter"));
+                    }
+                }
+            });
+    }
+}
+
+// Bars of the values in bins, over low-high widened to all the values. Returns the bar width too.
+fn histogram(values: &[f64], low: f64, high: f64, bins: usize) -> (Vec<Bar>, f64) {
+    let low = values.iter().copied().fold(low, f64::min);
+    let high = values.iter().copied().fold(high, f64::max);
+    let width = ((high - low) / bins as f64).max(f32::MIN_POSITIVE as f64);
+
+    let mut counts = vec![0u32; bins];
+    for value in values {
+        let bin = ((value - low) / width) as usize;
+        counts[bin.min(bins - 1)] += 1;
+    }
+
+    let bars = counts
+        .iter()
+        .enumerate()
+        .map(|(i, count)| Bar::new(low + (i as f64 + 0.5) * width, *count as f64))
+        .collect();
+    (bars, width)
+}
+
+impl Station {
+    // Batch and timed generation, every frame, whether the station is shown or not
+    pub fn update(&mut self, ctx: &egui::Context) {
+        // repaint often enough for sub-second test times too
+        ctx.request_repaint_after(std::time::Duration::from_secs_f64(
+            self.generator.testing_time.clamp(0.1, 1.0),
+        ));
+
+        // A batch runs a bit in every frame, so the GUI stays responsive.
+        // The timed generation waits until it's done.
+        if self.generator.batch.is_some() {
+            let stats = &mut self.stats;
+            let ret =
+                self.generator
+                    .batch_step(std::time::Duration::from_millis(50), |generator| {
+                        stats.add(
+                            &generator.multiboard,
+                            &generator.board_types[generator.multiboard.btype].tests,
+                        );
+                        stats.add_cycle(generator.last_cycle);
+                    });
+            match ret {
+                Ok(names) => {
+                    if let Some((_, files)) = &mut self.remote {
+                        let output_dir = &self.generator.output_dir;
+                        files.extend(
+                            names
+                                .iter()
+                                .map(|name| output_dir.join(name).display().to_string()),
+                        );
+                    }
+                }
+                Err(e) => {
+                    if let Some((request, _)) = self.remote.take() {
+                        request.respond(Err(format!("saving results failed: {e}")));
+                    }
+                    self.last_error = Some(format!("ERR: Saving results failed: {e}"));
+                }
+            }
+            self.update_preview();
+            ctx.request_repaint();
+        } else if self.enabled {
+            self.generator.save_retests(false);
+        }
+
+        if self.enabled && self.generator.batch.is_none() && self.generator.its_time() {
+            self.generate();
+        }
+
+        self.serve(ctx);
+    }
+
+    // (Re)starts the remote control on a new port, and does its requests.
+    // A request runs as a batch, and is answered when that's done (or cancelled).
+    // The next ones wait while a batch runs.
+    fn serve(&mut self, ctx: &egui::Context) {
+        if self.server_port != self.generator.remote_port {
+            self.server_port = self.generator.remote_port;
+            self.server = None; // frees the old port first
+            if let Some(port) = self.server_port {
+                let ctx = ctx.clone();
+                match Server::start(port, move || ctx.request_repaint()) {
+                    Ok(server) => self.server = Some(server),
+                    Err(e) => self.last_error = Some(format!("ERR: Remote control on {port}: {e}")),
+                }
+            }
+        }
+
+        if self.generator.batch.is_some() {
+            return;
+        }
+        if let Some((request, files)) = self.remote.take() {
+            request.respond(Ok(files));
+        }
+        if let Some(request) = self.server.as_ref().and_then(|server| server.poll()) {
+            self.generator.start_batch(request.count);
+            self.remote = Some((request, Vec::new()));
+        }
+    }
+
+    // One cycle (of generator.burst multiboards), on time or on demand. Not while a batch runs.
+    pub fn generate(&mut self) {
+        if self.generator.batch.is_some() {
+            return;
+        }
+
+        // Keep going on errors, it might be just a transient failure
+        let stats = &mut self.stats;
+        self.last_error = self
+            .generator
+            .generate_burst(|generator| {
+                stats.add(
+                    &generator.multiboard,
+                    &generator.board_types[generator.multiboard.btype].tests,
+                );
+                stats.add_cycle(generator.last_cycle);
+            })
+            .err()
+            .map(|e| format!("ERR: Saving results failed: {e}"));
+
+        self.update_preview();
+
+        if let Some(target) = self.target_count {
+            if self.generator.exported - self.target_start >= target {
+                self.set_enabled(false);
+            }
+        }
+    }
+
+    // Not counted in the statistics, the outcomes are forced
+    fn generate_pair(&mut self) {
+        self.last_error = self
+            .generator
+            .generate_pair()
+            .err()
+            .map(|e| format!("ERR: Saving results failed: {e}"));
+        self.update_preview();
+    }
+
+    // In a dry run the preview starts with the files the last cycles would have written
+    fn update_preview(&mut self) {
+        self.preview = self.generator.preview().unwrap_or_default();
+
+        let dry_run = self.generator.writer.take_dry_run();
+        if !dry_run.is_empty() {
+            self.preview = format!("{}\n\n{}", dry_run.join("\n"), self.preview);
+        }
+    }
+
+    // The results of the current multiboard are per test, so after tests of its type were
+    // added, removed or replaced, it's generated again for the preview
+    fn tests_replaced(&mut self, btype: usize) {
+        let multiboard = &self.generator.multiboard;
+        if multiboard.btype == btype && !multiboard.boards.is_empty() {
+            self.generator.generate_preview(self.generator.now());
+        }
+        self.update_preview();
+    }
+
+    // Generates a new multiboard, but doesn't save it or step the serials or the board type
+    pub fn preview(&mut self) {
+        self.generator.generate_preview(self.generator.now());
+        self.preview = self.generator.preview().unwrap_or_default();
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        // not while typing, the text fields have their own undo
+        let typing = ui.memory(|memory| memory.focus().is_some());
+        if !typing && ui.input_mut(|input| input.consume_key(Modifiers::COMMAND, Key::Z)) {
+            self.undo_tests();
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Output:");
+            if ui.text_edit_singleline(&mut self.output_dir_edit).changed() {
+                self.generator.output_dir = PathBuf::from(&self.output_dir_edit);
+            }
+            if ui.button("Browse").clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .set_directory(&self.generator.output_dir)
+                    .pick_folder()
+                {
+                    self.output_dir_edit = path.display().to_string();
+                    self.generator.output_dir = path;
+                }
+            }
+        });
+        if !self.generator.output_dir.is_dir() {
+            ui.label("The output directory does not exist yet, it will be created.");
+        }
+
+        ui.horizontal(|ui| {
+            let mut enabled = self.enabled;
+            if ui.checkbox(&mut enabled, "Enable").changed() {
+                self.set_enabled(enabled);
+            }
+            ui.checkbox(
+                &mut self.generator.golden_next,
+                "Next multiboard is golden (all nominal, passing)",
+            );
+        });
+        if let Some(error) = &self.last_error {
+            ui.colored_label(Color32::RED, error);
+        }
+        ui.monospace(format!("Last ID:{}", self.generator.last_id));
+        if let Some(rate) = self.rate() {
+            ui.horizontal(|ui| {
+                ui.monospace(format!("{rate:.1} boards/min"));
+
+                if let Some(target) = self.target_count {
+                    let done = self.generator.exported - self.target_start;
+                    let minutes = target.saturating_sub(done) as f64 / rate;
+                    let eta =
+                        chrono::Local::now() + chrono::Duration::seconds((minutes * 60.0) as i64);
+                    ui.monospace(format!("ETA: {}", eta.format(SIM_TIME_FORMAT)));
+                }
+            });
+        }
+        let pending = self.generator.writer.pending();
+        if pending > 0 {
+            ui.label(format!("{pending} files waiting to be written"));
+        }
+
+        ui.horizontal(|ui| {
+            let mut limited = self.target_count.is_some();
+            let mut target = self.target_count.unwrap_or(500);
+            let mut changed = ui.checkbox(&mut limited, "Stop after").changed();
+            if limited {
+                changed |= ui
+                    .add(egui::DragValue::new(&mut target).clamp_range(1..=u32::MAX))
+                    .changed();
+                ui.monospace(format!(
+                    "{}/{}",
+                    self.generator.exported - self.target_start,
+                    target
+                ));
+                if ui.button("Restart").clicked() {
+                    self.target_start = self.generator.exported;
+                }
+            }
+
+            if changed {
+                self.target_count = limited.then_some(target);
+                self.target_start = self.generator.exported;
+            }
+        });
+        ui.horizontal(|ui| {
+            let mut changed = ui
+                .add(
+                    egui::DragValue::new(&mut self.generator.testing_time)
+                        .clamp_range(0.1..=3600.0)
+                        .speed(0.1)
+                        .suffix(" s"),
+                )
+                .changed();
+            ui.label("Test time");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut self.generator.testing_jitter)
+                        .clamp_range(0.0..=100.0)
+                        .prefix("± ")
+                        .suffix("%"),
+                )
+                .changed();
+            ui.label("Jitter");
+
+            if changed {
+                self.generator.roll_interval();
+            }
+
+            ui.add(egui::DragValue::new(&mut self.generator.burst).clamp_range(1..=100))
+                .on_hover_text("Multiboards written at once, each test time");
+            ui.label("per cycle");
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.generator.board_time_min)
+                    .clamp_range(0.0..=3600.0)
+                    .speed(0.1)
+                    .suffix(" s"),
+            );
+            ui.label("-");
+            ui.add(
+                egui::DragValue::new(&mut self.generator.board_time_max)
+                    .clamp_range(0.0..=3600.0)
+                    .speed(0.1)
+                    .suffix(" s"),
+            );
+            ui.label("Board test time in the logs");
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.generator.board_delay)
+                    .clamp_range(0.0..=3600.0)
+                    .speed(0.1)
+                    .suffix(" s"),
+            );
+            ui.label("Delay between the boards of a panel");
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.generator.test_yield)
+                    .clamp_range(0.0..=100.0)
+                    .speed(0.1)
+                    .max_decimals(2)
+                    .suffix("%"),
+            );
+            ui.label("Yield per test (without an own fail rate)");
+        });
+        ui.add(egui::Slider::new(&mut self.generator.high_fail_ratio, 0..=100).text("High fail %"));
+        ui.add(
+            egui::Slider::new(&mut self.generator.marginal_rate, 0..=100)
+                .text("Marginal % (boards measuring close to the limits)"),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.generator.cluster_rate, 0..=100)
+                .text("Panel defect % (a test failing on neighbouring boards)"),
+        );
+        ui.horizontal(|ui| {
+            let mut cycling = self.generator.temperature_cycle.is_some();
+            let mut cycle = self.generator.temperature_cycle.unwrap_or_default();
+            ui.checkbox(&mut cycling, "Temperature cycle, ±");
+            if cycling {
+                ui.add(
+                    egui::DragValue::new(&mut cycle.amplitude)
+                        .clamp_range(0.0..=100.0)
+                        .suffix(" °C"),
+                );
+                ui.label("over");
+                ui.add(
+                    egui::DragValue::new(&mut cycle.period)
+                        .clamp_range(0.1..=10000.0)
+                        .suffix(" h"),
+                );
+                let now = self.generator.now();
+                ui.monospace(format!("now {:.1} °C", self.generator.temperature(now)));
+            }
+            self.generator.temperature_cycle = cycling.then_some(cycle);
+        });
+        ui.add(
+            egui::Slider::new(&mut self.generator.fail_margin, 0.0..=100.0)
+                .text("Fail margin % (failing measurements are at least this far out)"),
+        );
+        ui.horizontal(|ui| {
+            let (mut relative, mut step) = match self.generator.resolution {
+                Some(Resolution::Relative(percent)) => (true, percent),
+                Some(Resolution::Absolute(step)) => (false, step),
+                None => (true, 0.1),
+            };
+            let mut quantized = self.generator.resolution.is_some();
+            ui.checkbox(&mut quantized, "Resolution");
+            if quantized {
+                ui.add(limit_edit(&mut step));
+                ui.radio_value(&mut relative, true, "% of nom");
+                ui.radio_value(&mut relative, false, "absolute");
+            }
+
+            self.generator.resolution = quantized.then_some(if relative {
+                Resolution::Relative(step)
+            } else {
+                Resolution::Absolute(step)
+            });
+        });
+        ui.checkbox(
+            &mut self.generator.single_defect,
+            "Single defect: a failing board fails only one test",
+        );
+        ui.checkbox(
+            &mut self.generator.pin_detail,
+            "List open nodes of failed pins tests",
+        );
+        ui.horizontal(|ui| {
+            ui.label("Test order in the log:");
+            let order = &mut self.generator.test_order;
+            let custom = matches!(order, TestOrder::Custom(_));
+            ui.radio_value(order, TestOrder::Defined, "As defined");
+            ui.radio_value(order, TestOrder::Shuffled, "Shuffled");
+            let mut changed = ui.radio(custom, "Custom").clicked() && !custom;
+            if custom {
+                changed |= ui
+                    .add(
+                        egui::TextEdit::singleline(&mut self.test_order_edit)
+                            .hint_text("test names, first ones first"),
+                    )
+                    .changed();
+            }
+
+            if changed {
+                *order = TestOrder::Custom(
+                    self.test_order_edit
+                        .split(',')
+                        .map(|name| name.trim().to_string())
+                        .filter(|name| !name.is_empty())
+                        .collect(),
+                );
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if let Some(progress) = self.generator.batch_progress() {
+                ui.add(egui::ProgressBar::new(progress).show_percentage());
+                if ui.button("Cancel").clicked() {
+                    self.generator.stop_batch();
+                }
+            } else {
+                ui.add(egui::DragValue::new(&mut self.batch_count).clamp_range(1..=1_000_000));
+                ui.label("boards");
+                if ui.button("Generate now").clicked() {
+                    self.generator.start_batch(self.batch_count);
+                }
+                if ui
+                    .button("Generate pair")
+                    .on_hover_text("The same boards passing (PASS_) and failing (FAIL_)")
+                    .clicked()
+                {
+                    self.generate_pair();
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.generator.retest_rate)
+                    .clamp_range(0..=100)
+                    .suffix("%"),
+            );
+            ui.label("Retest failed boards after");
+            ui.add(
+                egui::DragValue::new(&mut self.generator.retest_delay)
+                    .clamp_range(0.0..=3600.0)
+                    .suffix(" s"),
+            );
+            if !self.generator.retests.is_empty() {
+                ui.label(format!("({} pending)", self.generator.retests.len()));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let mut fixed = self.generator.seed.is_some();
+            let mut changed = ui.checkbox(&mut fixed, "Fixed seed").changed();
+
+            let mut seed = self.generator.seed.unwrap_or_default();
+            if fixed {
+                changed |= ui.add(egui::DragValue::new(&mut seed)).changed();
+            }
+
+            if changed {
+                self.generator.seed = fixed.then_some(seed);
+                self.generator.reseed();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let mut remote = self.generator.remote_port.is_some();
+            ui.checkbox(&mut remote, "Remote control on port");
+            if remote {
+                let port = self.generator.remote_port.get_or_insert(8080);
+                ui.add(egui::DragValue::new(port).clamp_range(1..=u16::MAX));
+                ui.label(format!("POST http://127.0.0.1:{port}/generate?count=N"));
+            } else {
+                self.generator.remote_port = None;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let mut simulated = self.generator.sim_start.is_some();
+            let mut changed = ui
+                .checkbox(&mut simulated, "Simulated start time")
+                .changed();
+
+            if simulated {
+                changed |= ui.text_edit_singleline(&mut self.sim_start_edit).changed();
+            }
+
+            let start = parse_sim_time(&self.sim_start_edit);
+            if simulated && start.is_none() {
+                ui.colored_label(Color32::RED, "Format: YYYY-MM-DD HH:MM:SS");
+            } else if changed {
+                self.generator.set_sim_start(start.filter(|_| simulated));
+            }
+
+            if self.generator.sim_start.is_some() {
+                ui.monospace(format!(
+                    "Clock: {}",
+                    self.generator.last_export.format(SIM_TIME_FORMAT)
+                ));
+            }
+        });
+        if self.generator.sim_start.is_some() {
+            self.schedule_ui(ui);
+        }
+
+        ui.collapsing("Board types", |ui| {
+            self.board_types_ui(ui);
+        });
+
+        ui.collapsing("Forced failures", |ui| {
+            self.forced_failures_ui(ui);
+        });
+
+        ui.collapsing("Full scale", |ui| {
+            self.full_scale_ui(ui);
+        });
+
+        ui.collapsing("Statistics", |ui| {
+            self.stats_ui(ui);
+        });
+
+        ui.collapsing("Preview", |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Preview").clicked() {
+                    self.preview();
+                }
+                if ui
+                    .button("Self-test")
+                    .on_hover_text("Parses a log of each test type back, with these settings")
+                    .clicked()
+                {
+                    self.self_test = Some(parser::self_test(self.to_config()));
+                }
+            });
+            match &self.self_test {
+                Some(problems) if problems.is_empty() => {
+                    ui.colored_label(Color32::GREEN, "Self-test: OK");
+                }
+                Some(problems) => {
+                    ui.colored_label(Color32::RED, "Self-test failed:");
+                    for problem in problems {
+                        ui.colored_label(Color32::RED, problem);
+                    }
+                }
+                None => {}
+            }
+
+            egui::ScrollArea::vertical()
+                .id_source("preview_scroll")
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.preview.as_str())
+                            .code_editor()
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+        });
+
+        let mut changed = false;
+        ui.collapsing("Naming", |ui| {
+            egui::Grid::new("naming_grid").show(ui, |ui| {
+                ui.label("Line ID:");
+                changed |= ui
+                    .text_edit_singleline(&mut self.generator.line_id)
+                    .changed();
+                ui.end_row();
+                ui.label("Station ID:");
+                changed |= ui
+                    .text_edit_singleline(&mut self.generator.station_id)
+                    .changed();
+                ui.end_row();
+                ui.label("BTEST log level:");
+                changed |= ui
+                    .text_edit_singleline(&mut self.generator.btest.log_level)
+                    .changed();
+                ui.end_row();
+                ui.label("Operator:");
+                changed |= ui
+                    .text_edit_singleline(&mut self.generator.operator)
+                    .changed();
+                ui.end_row();
+                ui.label("Fixture ID:");
+                changed |= ui
+                    .text_edit_singleline(&mut self.generator.fixture_id)
+                    .changed();
+                ui.end_row();
+                ui.label("SW rev:");
+                changed |= ui
+                    .text_edit_singleline(&mut self.generator.sw_rev)
+                    .changed();
+                ui.end_row();
+                ui.label("Filename:");
+                changed |= ui
+                    .text_edit_singleline(&mut self.generator.filename_template)
+                    .changed();
+                ui.end_row();
+                ui.label("Extension:");
+                changed |= ui
+                    .add(
+                        egui::TextEdit::singleline(&mut self.generator.extension)
+                            .hint_text("none, or log, txt...")
+                            .desired_width(80.0),
+                    )
+                    .changed();
+                ui.end_row();
+            });
+            ui.horizontal(|ui| {
+                ui.label("BTEST flags:");
+                changed |= ui
+                    .checkbox(&mut self.generator.btest.learning, "Learning")
+                    .changed();
+                changed |= ui
+                    .checkbox(
+                        &mut self.generator.btest.known_good,
+                        "Known good (always set on golden boards)",
+                    )
+                    .changed();
+            });
+            ui.small(
+                "DMC: {line}, {year}, {doy}, {serial}, {index} | Filename: {index}, {time}, {counter}, {station}",
+            );
+            ui.horizontal(|ui| {
+                ui.label("Format:");
+                let format = &mut self.generator.output_format;
+                changed |= ui.radio_value(format, OutputFormat::Ict, "ICT").changed();
+                changed |= ui.radio_value(format, OutputFormat::Json, "JSON").changed();
+                changed |= ui.radio_value(format, OutputFormat::Both, "Both").changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Line endings:");
+                let ending = &mut self.generator.line_ending;
+                changed |= ui.radio_value(ending, LineEnding::Lf, "LF").changed();
+                changed |= ui.radio_value(ending, LineEnding::Crlf, "CRLF").changed();
+                changed |= ui
+                    .checkbox(
+                        &mut self.generator.trailing_newline,
+                        "Newline after the last brace",
+                    )
+                    .changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Board positions:");
+                let scheme = &mut self.generator.index_scheme;
+                changed |= ui
+                    .radio_value(scheme, IndexScheme::OneBased, "1, 2, 3")
+                    .changed();
+                changed |= ui
+                    .radio_value(scheme, IndexScheme::ZeroBased, "0, 1, 2")
+                    .changed();
+                changed |= ui
+                    .radio_value(scheme, IndexScheme::Letters, "A, B, C")
+                    .changed();
+            })
+            .response
+            .on_hover_text("In @PF, @BLOCK and the filenames");
+            ui.horizontal(|ui| {
+                let mut fixed = self.generator.precision.is_some();
+                let mut digits = self.generator.precision.unwrap_or(4);
+                changed |= ui.checkbox(&mut fixed, "Significant digits").changed();
+                if fixed {
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut digits).clamp_range(1..=9))
+                        .changed();
+                }
+                self.generator.precision = fixed.then_some(digits);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Subfolder per multiboard:");
+                let subfolders = &mut self.generator.subfolders;
+                changed |= ui
+                    .radio_value(subfolders, Subfolders::None, "None")
+                    .changed();
+                changed |= ui.radio_value(subfolders, Subfolders::Dmc, "DMC").changed();
+                changed |= ui
+                    .radio_value(subfolders, Subfolders::Time, "Time")
+                    .changed();
+            });
+            changed |= ui
+                .checkbox(
+                    &mut self.generator.daily_file,
+                    "Append the logs to one file per day (YYYY-MM-DD)",
+                )
+                .changed();
+            changed |= ui
+                .checkbox(&mut self.generator.summary, "Write summary.csv")
+                .changed();
+            changed |= ui
+                .checkbox(
+                    &mut self.generator.csv_export,
+                    "Write a <DMC>.csv per board",
+                )
+                .changed();
+            changed |= ui
+                .checkbox(
+                    &mut self.generator.iso_timestamps,
+                    "ISO 8601 timestamps in the JSON and summary.csv",
+                )
+                .changed();
+            ui.horizontal(|ui| {
+                let mut corrupt = self.generator.corrupt_rate > 0;
+                changed |= ui
+                    .checkbox(&mut corrupt, "Corrupt logs, for testing parsers")
+                    .on_hover_text("Some logs are cut off mid-block, as if the tester crashed")
+                    .changed();
+                if corrupt {
+                    let rate = &mut self.generator.corrupt_rate;
+                    *rate = (*rate).max(1);
+                    changed |= ui
+                        .add(egui::Slider::new(rate, 1..=100).text("% of the logs"))
+                        .changed();
+                } else {
+                    self.generator.corrupt_rate = 0;
+                }
+            });
+            ui.checkbox(
+                &mut self.generator.writer.dry_run,
+                "Dry run: only list the files in the preview, without writing them",
+            );
+            ui.horizontal(|ui| {
+                let mut retries = self.generator.writer.retries();
+                ui.label("Retries of a failed write:");
+                if ui
+                    .add(egui::DragValue::new(&mut retries).clamp_range(0..=10))
+                    .on_hover_text("For flaky network shares, each retry waits twice as long")
+                    .changed()
+                {
+                    self.generator.writer.set_retries(retries);
+                }
+            });
+        });
+        if changed {
+            self.update_preview();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn remote_request_as_batch() {
+        let port = 40_000 + (std::process::id() % 10_000) as u16;
+        let mut station = Station::from_config(Config {
+            seed: Some(1),
+            dry_run: true,
+            remote_port: Some(port),
+            ..Default::default()
+        });
+        let ctx = egui::Context::default();
+        station.serve(&ctx);
+
+        let (reply_tx, reply) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+            write!(stream, "POST /generate?count=10000 HTTP/1.1\r\n\r\n").unwrap();
+            let mut response = String::new();
+            let _ = stream.read_to_string(&mut response);
+            let _ = reply_tx.send(response);
+        });
+
+        // picked up by a serve call, which only starts the batch
+        let started = Instant::now();
+        while station.generator.batch.is_none() && started.elapsed().as_secs() < 5 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            station.serve(&ctx);
+        }
+        assert!(station.generator.batch.is_some());
+        assert_eq!(station.generator.exported, 0);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(reply.try_recv().is_err());
+
+        // answered once the batch is over
+        station.generator.stop_batch();
+        station.serve(&ctx);
+        let response = reply
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.ends_with(r#"{"files":[]}"#), "{response}");
+    }
+}
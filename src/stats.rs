@@ -0,0 +1,116 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::generator::{MultiBoard, Test};
+
+const ROLLING_WINDOW: usize = 100; // boards
+const MEASUREMENT_BUFFER: usize = 1000; // measurements kept per test
+const CYCLE_BUFFER: usize = 1000; // cycle times kept
+
+// Recent measurements of a test, for the histogram
+#[derive(Default)]
+pub struct Measurements {
+    pub values: VecDeque<f32>,
+    pub limits: Option<(f32, f32, f32)>, // (min, nom, max)
+}
+
+// Running totals of the exported boards (first tests only, retests are not counted)
+#[derive(Default)]
+pub struct Stats {
+    pub boards: u32,
+    pub passed: u32,
+    pub failed: u32,
+    pub recent: VecDeque<bool>, // results of the last ROLLING_WINDOW boards
+    pub test_fails: HashMap<String, u32>, // test name -> fail count
+    pub measurements: HashMap<String, Measurements>, // test name -> recent measurements
+    pub cycle_times: VecDeque<f64>, // in seconds, testing times of the last CYCLE_BUFFER cycles
+}
+
+impl Stats {
+    pub fn add(&mut self, multiboard: &MultiBoard, tests: &[Test]) {
+        // golden boards are references, they would skew the yield
+        if multiboard.golden {
+            return;
+        }
+
+        for board in &multiboard.boards {
+            let ok = board.get_result() == "00";
+
+            self.boards += 1;
+            if ok {
+                self.passed += 1;
+            } else {
+                self.failed += 1;
+            }
+
+            self.recent.push_back(ok);
+            if self.recent.len() > ROLLING_WINDOW {
+                self.recent.pop_front();
+            }
+
+            for (test, result) in tests.iter().zip(board.results.iter()) {
+                if result.tested && !result.ok {
+                    *self.test_fails.entry(test.name.clone()).or_default() += 1;
+                }
+
+                // the extra measurements are kept apart, as "name (unit)"
+                let specs = test.get_specs();
+                let own = specs.len() - test.extra.len().min(specs.len());
+                for (i, (spec, value)) in specs.iter().zip(&result.measured).enumerate() {
+                    let name = if i < own {
+                        test.name.clone()
+                    } else {
+                        format!("{} ({})", test.name, spec.unit)
+                    };
+                    let m = self.measurements.entry(name).or_default();
+                    m.limits = Some((spec.min, spec.nom, spec.max));
+                    m.values.push_back(*value);
+                    if m.values.len() > MEASUREMENT_BUFFER {
+                        m.values.pop_front();
+                    }
+                }
+            }
+        }
+    }
+
+    // Every cycle counts, even the golden ones, since the tester is busy with them as well
+    pub fn add_cycle(&mut self, seconds: f64) {
+        self.cycle_times.push_back(seconds);
+        if self.cycle_times.len() > CYCLE_BUFFER {
+            self.cycle_times.pop_front();
+        }
+    }
+
+    // (min, avg, max) of the recent cycle times, None if there are none yet
+    pub fn cycle_summary(&self) -> Option<(f64, f64, f64)> {
+        let times = &self.cycle_times;
+        let min = times.iter().copied().reduce(f64::min)?;
+        let max = times.iter().copied().reduce(f64::max)?;
+        let avg = times.iter().sum::<f64>() / times.len() as f64;
+        Some((min, avg, max))
+    }
+
+    // in %, None if there are no boards yet
+    pub fn total_yield(&self) -> Option<f32> {
+        (self.boards > 0).then(|| self.passed as f32 / self.boards as f32 * 100.0)
+    }
+
+    // in %, over the last ROLLING_WINDOW boards
+    pub fn rolling_yield(&self) -> Option<f32> {
+        let passed = self.recent.iter().filter(|ok| **ok).count();
+        (!self.recent.is_empty()).then(|| passed as f32 / self.recent.len() as f32 * 100.0)
+    }
+
+    // Sorted names of the tests with measurements
+    pub fn measured_tests(&self) -> Vec<&String> {
+        let mut ret: Vec<&String> = self.measurements.keys().collect();
+        ret.sort();
+        ret
+    }
+
+    // Most failing tests first
+    pub fn top_fails(&self) -> Vec<(&String, &u32)> {
+        let mut ret: Vec<(&String, &u32)> = self.test_fails.iter().collect();
+        ret.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        ret
+    }
+}
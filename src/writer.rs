@@ -0,0 +1,271 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+enum Job {
+    Write(PathBuf, String),
+    Append(PathBuf, String, String), // path, header for a new file, lines
+}
+
+// Writes the files on a background thread, so a slow disk doesn't stall the GUI.
+// The jobs are done in order, so if a cycle comes while the previous one is still being
+// written, its files are just queued after it. Errors are collected, see take_error.
+// A failed write is tried again a few times, with a growing delay, before it counts as an
+// error, see set_retries. Meanwhile the file stays pending, and the later ones wait behind it.
+pub struct Writer {
+    jobs: Option<Sender<Job>>,
+    errors: Receiver<io::Error>,
+    pending: Arc<(Mutex<usize>, Condvar)>, // queued jobs, including the one in flight
+    thread: Option<JoinHandle<()>>,
+    written: Arc<Mutex<Vec<PathBuf>>>, // files written since the start, see written
+    retries: Arc<AtomicU32>,
+
+    // Nothing is written in a dry run, the paths are just collected, see take_dry_run
+    pub dry_run: bool,
+    dry_run_paths: RefCell<Vec<String>>,
+}
+
+// The oldest paths are dropped after this many, so a long run doesn't grow the list forever
+pub const MAX_WRITTEN: usize = 1000;
+
+pub const DEFAULT_RETRIES: u32 = 3;
+
+// Delay before the first retry, doubled for each next one, up to MAX_BACKOFF
+const BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+// The directories are created as needed, so a missing output_dir is not an error
+fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => std::fs::create_dir_all(dir),
+        _ => Ok(()),
+    }
+}
+
+// Tries the job up to 1 + retries times
+fn run_with_retry(job: &Job, retries: u32) -> io::Result<()> {
+    let mut backoff = BACKOFF;
+    let mut attempt = 0;
+    loop {
+        match run(job) {
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                log::warn!("writing failed: {e}, retry {attempt}/{retries} in {backoff:?}");
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            ret => return ret,
+        }
+    }
+}
+
+fn run(job: &Job) -> io::Result<()> {
+    match job {
+        Job::Write(path, _) | Job::Append(path, ..) => create_parent(path)?,
+    }
+
+    match job {
+        Job::Write(path, contents) => std::fs::write(path, contents),
+        Job::Append(path, header, lines) => {
+            let new_file = !path.exists();
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+
+            if new_file && !header.is_empty() {
+                writeln!(file, "{header}")?;
+            }
+            file.write_all(lines.as_bytes())
+        }
+    }
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        let (jobs, job_rx) = mpsc::channel::<Job>();
+        let (error_tx, errors) = mpsc::channel();
+        let pending = Arc::new((Mutex::new(0usize), Condvar::new()));
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let retries = Arc::new(AtomicU32::new(DEFAULT_RETRIES));
+
+        let counter = pending.clone();
+        let paths = written.clone();
+        let max_retries = retries.clone();
+        let thread = std::thread::spawn(move || {
+            for job in job_rx {
+                let path = match &job {
+                    Job::Write(path, _) | Job::Append(path, ..) => path.clone(),
+                };
+                match run_with_retry(&job, max_retries.load(Ordering::Relaxed)) {
+                    Ok(()) => {
+                        let mut paths = paths.lock().unwrap();
+                        // appended files are listed once
+                        if !paths.contains(&path) {
+                            if paths.len() >= MAX_WRITTEN {
+                                paths.remove(0);
+                            }
+                            paths.push(path);
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("writing failed: {e}");
+                        let _ = error_tx.send(e);
+                    }
+                }
+
+                let (count, done) = &*counter;
+                *count.lock().unwrap() -= 1;
+                done.notify_all();
+            }
+        });
+
+        Self {
+            jobs: Some(jobs),
+            errors,
+            pending,
+            thread: Some(thread),
+            written,
+            retries,
+            dry_run: false,
+            dry_run_paths: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn queue(&self, job: Job) {
+        if self.dry_run {
+            let (verb, path, len) = match &job {
+                Job::Write(path, contents) => ("write", path, contents.len()),
+                Job::Append(path, _, lines) => ("append to", path, lines.len()),
+            };
+            let line = format!("Would {verb} {} ({len} bytes)", path.display());
+            log::info!("dry run: {line}");
+            self.dry_run_paths.borrow_mut().push(line);
+            return;
+        }
+
+        match &job {
+            Job::Write(path, contents) => log::debug!("write {path:?}, {} bytes", contents.len()),
+            Job::Append(path, _, lines) => log::debug!("append {path:?}, {} bytes", lines.len()),
+        }
+
+        *self.pending.0.lock().unwrap() += 1;
+        if let Some(jobs) = &self.jobs {
+            if jobs.send(job).is_err() {
+                // the thread is gone, nothing will be written
+                *self.pending.0.lock().unwrap() -= 1;
+            }
+        }
+    }
+
+    pub fn write(&self, path: PathBuf, contents: String) {
+        self.queue(Job::Write(path, contents));
+    }
+
+    // Appends the lines to the file, and writes the header (if any) first if the file is new
+    pub fn append(&self, path: PathBuf, header: String, lines: String) {
+        self.queue(Job::Append(path, header, lines));
+    }
+
+    pub fn pending(&self) -> usize {
+        *self.pending.0.lock().unwrap()
+    }
+
+    // Blocks until every queued file is written
+    pub fn flush(&self) {
+        let (count, done) = &*self.pending;
+        let mut count = count.lock().unwrap();
+        while *count > 0 {
+            count = done.wait(count).unwrap();
+        }
+    }
+
+    // How many times a failed write is tried again, from the next write on
+    pub fn set_retries(&self, retries: u32) {
+        self.retries.store(retries, Ordering::Relaxed);
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    // Files written so far, the oldest first
+    pub fn written(&self) -> Vec<PathBuf> {
+        self.written.lock().unwrap().clone()
+    }
+
+    // What the dry run would have written since the last call
+    pub fn take_dry_run(&self) -> Vec<String> {
+        self.dry_run_paths.take()
+    }
+
+    // Returns the first error since the last call, and drops the rest
+    pub fn take_error(&self) -> io::Result<()> {
+        match self.errors.try_iter().next() {
+            Some(e) => {
+                self.errors.try_iter().for_each(drop);
+                Err(e)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for Writer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Writer {
+    // Finishes the queued writes before exiting
+    fn drop(&mut self) {
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Unique per test and run, removed by the test
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ict_log_maker_{name}_{}", std::process::id()))
+    }
+
+    #[test]
+    fn creates_directories() {
+        let dir = temp_dir("creates_directories");
+        assert!(!dir.exists());
+        let (log, daily) = (
+            dir.join("out").join("log"),
+            dir.join("daily").join("2024-02-03"),
+        );
+
+        let writer = Writer::new();
+        writer.write(log.clone(), "{@BATCH}".to_string());
+        writer.append(daily.clone(), String::new(), "{@BATCH}".to_string());
+        writer.flush();
+
+        let ret = writer.take_error();
+        let contents = (
+            std::fs::read_to_string(&log),
+            std::fs::read_to_string(&daily),
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(ret.is_ok());
+        assert_eq!(contents.0.unwrap(), "{@BATCH}");
+        assert_eq!(contents.1.unwrap(), "{@BATCH}");
+        assert_eq!(writer.written(), [log, daily]);
+    }
+}